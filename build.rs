@@ -1,3 +1,4 @@
+use std::env;
 use std::path::PathBuf;
 
 use embuild::{
@@ -7,6 +8,11 @@ use embuild::{
 };
 
 fn main() -> anyhow::Result<()> {
+    // The host simulator does not use ESP-IDF at all.
+    if env::var_os("CARGO_FEATURE_HOST").is_some() {
+        return Ok(());
+    }
+
     // Necessary because of this issue: https://github.com/rust-lang/cargo/issues/9641
     LinkArgs::output_propagated("ESP_IDF")?;
 
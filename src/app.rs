@@ -0,0 +1,1604 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use log::*;
+
+use crate::button::gestures::DoublePresses;
+use crate::button::ButtonId;
+use crate::button_controllers::{Binding, Command, Controller, DisplayState, InvalidLabels, Press};
+use crate::config;
+use crate::display::DisplayCommand;
+use crate::homeassistant::HomeAssistant;
+use crate::messages::Message;
+use crate::mqtt::{Label, QoS};
+
+const BLANK_TIMEOUT: Duration = Duration::from_secs(10);
+const FOCUS_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub trait MqttOutput {
+    fn subscribe(&self, topic: &str, label: Label, qos: QoS);
+    fn unsubscribe(&self, topic: &str, label: Label);
+    fn publish(&self, topic: &str, qos: QoS, retain: bool, data: &str);
+    fn offline(&self);
+}
+
+pub trait DisplayOutput {
+    fn send(&self, command: DisplayCommand);
+}
+
+impl DisplayOutput for mpsc::Sender<DisplayCommand> {
+    fn send(&self, command: DisplayCommand) {
+        mpsc::Sender::send(self, command).unwrap();
+    }
+}
+
+pub trait Timer {
+    fn start(&mut self, duration: Duration);
+    fn cancel(&mut self);
+}
+
+pub trait ConfigStore {
+    fn save(&mut self, config: &str) -> anyhow::Result<()>;
+    fn save_state(&mut self, key: &str, state: &str) -> anyhow::Result<()>;
+    fn load_state(&self, key: &str) -> anyhow::Result<Option<String>>;
+}
+
+enum TimeOfDay {
+    Day,
+    Night,
+}
+
+struct RequestedDisplayStatus {
+    time_of_day: TimeOfDay,
+    forced_on: bool,
+    night_timer: bool,
+}
+
+struct ActualDisplayStatus {
+    timer_on: bool,
+    display_on: bool,
+}
+
+impl RequestedDisplayStatus {
+    fn get_timer_required(&self) -> bool {
+        self.night_timer
+    }
+
+    fn get_display_required(&self) -> bool {
+        matches!(self.time_of_day, TimeOfDay::Day) || self.forced_on || self.night_timer
+    }
+
+    fn turn_night_timer_on(&mut self) {
+        self.night_timer = true;
+    }
+
+    fn turn_night_timer_off(&mut self) {
+        self.night_timer = false;
+    }
+}
+
+enum CommandStatus {
+    Pending {
+        topics: Vec<String>,
+        deadline: Instant,
+    },
+    Failed,
+}
+
+/// A tile that page up and page down adjust, until another button is
+/// pressed or it is left alone for `FOCUS_TIMEOUT`.
+struct Focus {
+    id: usize,
+    deadline: Instant,
+}
+
+/// A press waiting for a second press of the same kind to confirm it.
+struct Confirm {
+    id: usize,
+    press: Press,
+    deadline: Instant,
+}
+
+struct ScheduledCommand {
+    at: Instant,
+    command: Command,
+}
+
+pub struct App<M, D, T, S> {
+    mqtt: M,
+    display: D,
+    timer: T,
+    storage: S,
+    /// Tells the gesture recognizer which buttons wait for a double press.
+    double_presses: DoublePresses,
+    num_per_page: usize,
+    config_json: String,
+    config: config::RemoteConfig,
+    controllers: Vec<Box<dyn Controller>>,
+    command_status: HashMap<usize, CommandStatus>,
+    /// Labels whose last message couldn't be used, for each controller.
+    invalid: HashMap<usize, InvalidLabels>,
+    /// What was last saved for each controller that keeps state.
+    saved_states: HashMap<usize, String>,
+    scheduled: Vec<ScheduledCommand>,
+    focus: Option<Focus>,
+    confirm: Option<Confirm>,
+    /// Buttons whose press turned the display on, so the gesture that
+    /// follows is ignored.
+    waking_presses: HashSet<usize>,
+    page_num: usize,
+    requested_display_status: RequestedDisplayStatus,
+    status: ActualDisplayStatus,
+    remote_id: String,
+    homeassistant: HomeAssistant,
+    mqtt_connected: bool,
+    restart_required: bool,
+}
+
+impl<M, D, T, S> App<M, D, T, S>
+where
+    M: MqttOutput,
+    D: DisplayOutput,
+    T: Timer,
+    S: ConfigStore,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        mqtt: M,
+        display: D,
+        timer: T,
+        storage: S,
+        double_presses: DoublePresses,
+        num_per_page: usize,
+        remote_id: String,
+        config_json: String,
+        config: config::RemoteConfig,
+    ) -> Self {
+        let controllers = create_controllers(&config);
+        let homeassistant =
+            HomeAssistant::new(&remote_id, &config.get_availability_topic(&remote_id));
+
+        App {
+            mqtt,
+            display,
+            timer,
+            storage,
+            double_presses,
+            num_per_page,
+            config_json,
+            config,
+            controllers,
+            command_status: HashMap::new(),
+            invalid: HashMap::new(),
+            saved_states: HashMap::new(),
+            scheduled: Vec::new(),
+            focus: None,
+            confirm: None,
+            waking_presses: HashSet::new(),
+            page_num: 0,
+            requested_display_status: RequestedDisplayStatus {
+                time_of_day: TimeOfDay::Day,
+                forced_on: false,
+                night_timer: false,
+            },
+            status: ActualDisplayStatus {
+                display_on: true,
+                timer_on: false,
+            },
+            remote_id,
+            homeassistant,
+            mqtt_connected: false,
+            restart_required: false,
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.restore_states();
+        self.subscribe_controllers();
+        self.do_blank(false);
+
+        self.display.send(DisplayCommand::Started);
+        self.display.send(DisplayCommand::ShowPage(self.page_num));
+        self.update_displays();
+    }
+
+    pub fn process_message(&mut self, message: Message) {
+        match message {
+            Message::MqttReceived(_, power, Label::NightStatus) => {
+                info!("Got night: {}", String::from_utf8_lossy(&power));
+                match power.as_slice() {
+                    b"ON" => self.requested_display_status.time_of_day = TimeOfDay::Night,
+                    b"OFF" => self.requested_display_status.time_of_day = TimeOfDay::Day,
+                    _ => {}
+                };
+                // do_blank publishes the status itself if the display changes.
+                let display_on = self.status.display_on;
+                self.do_blank(false);
+                if self.status.display_on == display_on {
+                    self.publish_status();
+                }
+            }
+            Message::MqttReceived(_, data, Label::Config) => match String::from_utf8(data) {
+                Ok(data) => self.process_config(data),
+                Err(err) => error!("Got invalid config: {}", err),
+            },
+            // Anything else, such as an empty payload clearing a retained
+            // message, must not restart the remote or it will do so forever.
+            Message::MqttReceived(_, data, Label::Restart) => {
+                if data == b"restart" {
+                    info!("Got restart request");
+                    self.restart();
+                } else {
+                    error!(
+                        "Ignoring restart request: {}",
+                        String::from_utf8_lossy(&data)
+                    );
+                }
+            }
+            Message::MqttReceived(topic, data, Label::Button(id, sid)) => {
+                info!(
+                    "Got message: {} - {}",
+                    topic,
+                    String::from_utf8_lossy(&data)
+                );
+                if id >= self.controllers.len() {
+                    error!("Controller for message {} does not exist", id);
+                    return;
+                }
+                let old_state = self.get_controller_state(id);
+                let old_extra = self.controllers[id].get_extra();
+                let old_name = self.controllers[id].get_display_name();
+                let controller = self.controllers[id].as_mut();
+                let invalid = self.invalid.entry(id).or_default();
+                invalid.process_message(controller, sid, data);
+                let state = self.get_controller_state(id);
+                let extra = self.controllers[id].get_extra();
+                let name = self.controllers[id].get_display_name();
+                if Some(id) == self.config.night_controller {
+                    match state {
+                        DisplayState::Off => self.requested_display_status.forced_on = false,
+                        DisplayState::HardOff => self.requested_display_status.forced_on = false,
+                        DisplayState::On => self.requested_display_status.forced_on = true,
+                        DisplayState::OnOther => self.requested_display_status.forced_on = true,
+                        DisplayState::Error => {}
+                        DisplayState::Unknown => {}
+                        DisplayState::Pending => {}
+                    }
+                    self.do_blank(false);
+                }
+
+                // Any reply ends a pending command, even one that leaves the
+                // state as it was.
+                let pending = matches!(
+                    self.command_status.get(&id),
+                    Some(CommandStatus::Pending { .. })
+                );
+                if pending {
+                    self.command_status.remove(&id);
+                }
+
+                if old_state != state || old_extra != extra || old_name != name {
+                    self.command_status.remove(&id);
+                    // What was being confirmed may no longer apply.
+                    if matches!(&self.confirm, Some(confirm) if confirm.id == id) {
+                        self.confirm = None;
+                    }
+                    self.redraw_controller(id);
+                } else if pending {
+                    self.redraw_controller(id);
+                }
+            }
+            Message::MqttConnect => {
+                info!("Got connected");
+                self.mqtt_connected = true;
+                self.publish_discovery();
+                self.publish_status();
+            }
+            Message::MqttDisconnect => {
+                info!("Got disconnected");
+                self.mqtt_connected = false;
+                for controller in self.controllers.iter_mut() {
+                    controller.process_disconnected();
+                }
+                self.invalid.clear();
+                self.command_status.clear();
+                self.update_displays();
+            }
+            Message::MqttPublishFailed(topic) => {
+                error!("Command to {} was not delivered", topic);
+                self.fail_commands(|topics, _| topics.contains(&topic));
+            }
+            Message::Tick => {
+                let now = Instant::now();
+                self.tick_controllers(now);
+                self.save_states();
+                self.publish_scheduled(now);
+                self.fail_commands(|_, deadline| deadline <= now);
+                if matches!(&self.focus, Some(focus) if focus.deadline <= now) {
+                    self.unfocus();
+                }
+                if matches!(&self.confirm, Some(confirm) if confirm.deadline <= now) {
+                    self.cancel_confirm();
+                }
+            }
+            Message::MqttState(state) => {
+                self.display.send(DisplayCommand::MqttState(state));
+            }
+            Message::ButtonPress(ButtonId::Physical(id_in_page)) => {
+                if self.status.display_on {
+                    let id = self.page_to_controller_id(self.page_num, id_in_page);
+                    if !self.has_bindings(id) {
+                        self.button_press(id, Press::Short);
+                    }
+                    self.display.send(DisplayCommand::ButtonPressed(id_in_page));
+                } else {
+                    self.waking_presses.insert(id_in_page);
+                }
+                self.requested_display_status.turn_night_timer_on();
+                self.do_blank(true);
+            }
+            Message::ButtonPress(ButtonId::Controller(id)) => {
+                if !self.has_bindings(id) {
+                    self.button_press(id, Press::Short);
+                }
+                let (msg_page_num, id_in_page) = self.controller_to_page_id(id);
+                if msg_page_num == self.page_num {
+                    self.display.send(DisplayCommand::ButtonPressed(id_in_page));
+                }
+                self.requested_display_status.turn_night_timer_on();
+                self.do_blank(true);
+            }
+            Message::ButtonPress(ButtonId::PageUp) if self.focus.is_some() => {
+                self.adjust_focused(true);
+                self.requested_display_status.turn_night_timer_on();
+                self.do_blank(true);
+            }
+            Message::ButtonPress(ButtonId::PageDown) if self.focus.is_some() => {
+                self.adjust_focused(false);
+                self.requested_display_status.turn_night_timer_on();
+                self.do_blank(true);
+            }
+            Message::ButtonPress(ButtonId::PageUp) => {
+                info!("got page up");
+                let last_page = self.get_last_page();
+                self.page_num = self.page_num.saturating_add(1);
+                if self.page_num > last_page {
+                    self.page_num = last_page
+                };
+                self.display.send(DisplayCommand::ShowPage(self.page_num));
+                self.update_displays();
+                self.publish_status();
+                self.requested_display_status.turn_night_timer_on();
+                self.do_blank(true);
+            }
+            Message::ButtonPress(ButtonId::PageDown) => {
+                info!("got page down");
+                self.page_num = self.page_num.saturating_sub(1);
+                self.display.send(DisplayCommand::ShowPage(self.page_num));
+                self.update_displays();
+                self.publish_status();
+                self.requested_display_status.turn_night_timer_on();
+                self.do_blank(true);
+            }
+            Message::ButtonPress(ButtonId::NotAButton) => {
+                info!("Got not a button press");
+                self.requested_display_status.turn_night_timer_on();
+                self.do_blank(true);
+            }
+            Message::ButtonRelease(ButtonId::Physical(id_in_page)) => {
+                info!("Got button release");
+                self.display
+                    .send(DisplayCommand::ButtonReleased(id_in_page));
+                self.requested_display_status.turn_night_timer_on();
+                self.do_blank(true);
+            }
+            Message::ButtonRelease(ButtonId::Controller(id)) => {
+                info!("Got button release");
+                let (msg_page_num, id_in_page) = self.controller_to_page_id(id);
+                if msg_page_num == self.page_num {
+                    self.display
+                        .send(DisplayCommand::ButtonReleased(id_in_page));
+                }
+                self.requested_display_status.turn_night_timer_on();
+                self.do_blank(true);
+            }
+            Message::ButtonRelease(_) => {
+                info!("Got button release");
+                self.requested_display_status.turn_night_timer_on();
+                self.do_blank(true);
+            }
+            Message::ShortPress(id) => self.gesture_press(id, Press::Short),
+            Message::LongPress(id) => self.gesture_press(id, Press::Long),
+            Message::DoublePress(id) => self.gesture_press(id, Press::Double),
+            Message::BlankDisplays => {
+                info!("Got blank display timer");
+                self.unfocus();
+                self.cancel_confirm();
+                self.requested_display_status.turn_night_timer_off();
+                self.do_blank(true);
+            }
+        }
+    }
+
+    fn process_config(&mut self, data: String) {
+        if data == self.config_json {
+            info!("Got unchanged config");
+            return;
+        }
+
+        let new_config = match config::parse_config(&data) {
+            Ok(new_config) => new_config,
+            Err(err) => {
+                error!("Got invalid config: {}", err);
+                return;
+            }
+        };
+
+        info!("Got new config");
+        if let Err(err) = self.storage.save(&data) {
+            error!("Cannot save config: {}", err);
+        }
+
+        // The last will, maximum message size and reconnect policy are fixed
+        // when the MQTT client is created.
+        let availability_topic = new_config.get_availability_topic(&self.remote_id);
+        if availability_topic != self.config.get_availability_topic(&self.remote_id)
+            || new_config.get_max_message_size() != self.config.get_max_message_size()
+            || new_config.get_reconnect_policy() != self.config.get_reconnect_policy()
+        {
+            info!("MQTT settings changed, restarting");
+            self.restart();
+            return;
+        }
+
+        self.unfocus();
+        self.confirm = None;
+        self.unsubscribe_controllers();
+        if self.mqtt_connected {
+            for index in new_config.controllers.len()..self.controllers.len() {
+                let removal = self.homeassistant.get_event_removal(index);
+                self.mqtt
+                    .publish(&removal.topic, QoS::AtMostOnce, true, &removal.payload);
+            }
+        }
+        self.controllers = create_controllers(&new_config);
+        self.invalid.clear();
+        self.command_status.clear();
+        // Steps still to come belong to macros that may have changed.
+        self.scheduled.clear();
+        self.waking_presses.clear();
+        self.saved_states.clear();
+        self.restore_states();
+        self.config = new_config;
+        self.config_json = data;
+        self.subscribe_controllers();
+
+        let last_page = self.get_last_page();
+        if self.page_num > last_page {
+            self.page_num = last_page
+        };
+        self.display.send(DisplayCommand::ShowPage(self.page_num));
+        self.update_displays();
+
+        if self.mqtt_connected {
+            self.publish_discovery();
+            self.publish_status();
+        }
+    }
+
+    pub fn restart_required(&self) -> bool {
+        self.restart_required
+    }
+
+    fn restart(&mut self) {
+        self.mqtt.offline();
+        self.restart_required = true;
+    }
+
+    fn publish_discovery(&self) {
+        for discovery in self.homeassistant.get_discovery(&self.controllers) {
+            self.mqtt
+                .publish(&discovery.topic, QoS::AtMostOnce, true, &discovery.payload);
+        }
+    }
+
+    fn publish_status(&self) {
+        if !self.mqtt_connected {
+            return;
+        }
+
+        let page = self.page_num.to_string();
+        self.mqtt.publish(
+            &self.homeassistant.page_topic(),
+            QoS::AtMostOnce,
+            true,
+            &page,
+        );
+
+        let display = if self.status.display_on { "ON" } else { "OFF" };
+        self.mqtt.publish(
+            &self.homeassistant.display_topic(),
+            QoS::AtMostOnce,
+            true,
+            display,
+        );
+
+        let night = match self.requested_display_status.time_of_day {
+            TimeOfDay::Day => "OFF",
+            TimeOfDay::Night => "ON",
+        };
+        self.mqtt.publish(
+            &self.homeassistant.night_topic(),
+            QoS::AtMostOnce,
+            true,
+            night,
+        );
+    }
+
+    fn subscribe_controllers(&self) {
+        for (index, f) in self.controllers.iter().enumerate() {
+            let subscriptions = f.get_subscriptions();
+            for s in subscriptions {
+                let label = Label::Button(index, s.label);
+                info!("Subscribing to {}.", s.topic);
+                self.mqtt.subscribe(&s.topic, label, s.qos);
+            }
+        }
+
+        if let Some(night_topic) = &self.config.night_topic {
+            self.mqtt
+                .subscribe(night_topic, Label::NightStatus, QoS::AtMostOnce);
+        }
+    }
+
+    fn unsubscribe_controllers(&self) {
+        for (index, f) in self.controllers.iter().enumerate() {
+            let subscriptions = f.get_subscriptions();
+            for s in subscriptions {
+                let label = Label::Button(index, s.label);
+                info!("Unsubscribing from {}.", s.topic);
+                self.mqtt.unsubscribe(&s.topic, label);
+            }
+        }
+
+        if let Some(night_topic) = &self.config.night_topic {
+            self.mqtt.unsubscribe(night_topic, Label::NightStatus);
+        }
+    }
+
+    fn get_display_state(&self, id: usize) -> DisplayState {
+        // Shown as pending too, so displays without names, such as the LED
+        // ring, show it.
+        if matches!(&self.confirm, Some(confirm) if confirm.id == id) {
+            return DisplayState::Pending;
+        }
+
+        match self.command_status.get(&id) {
+            Some(CommandStatus::Pending { .. }) => DisplayState::Pending,
+            Some(CommandStatus::Failed) => DisplayState::Error,
+            None => self.get_controller_state(id),
+        }
+    }
+
+    /// The controller's own state, or an error if its last message on any
+    /// topic couldn't be used.
+    fn get_controller_state(&self, id: usize) -> DisplayState {
+        let state = self.controllers[id].get_display_state();
+        match self.invalid.get(&id) {
+            Some(invalid) => invalid.get_display_state(state),
+            None => state,
+        }
+    }
+
+    /// Turn pending commands into errors if `failed` says so.
+    fn fail_commands(&mut self, failed: impl Fn(&[String], Instant) -> bool) {
+        let ids: Vec<usize> = self
+            .command_status
+            .iter()
+            .filter_map(|(id, status)| match status {
+                CommandStatus::Pending { topics, deadline } if failed(topics, *deadline) => {
+                    Some(*id)
+                }
+                _ => None,
+            })
+            .collect();
+
+        for id in ids {
+            error!("Controller {} did not respond to command", id);
+            self.command_status.insert(id, CommandStatus::Failed);
+            self.redraw_controller(id);
+        }
+    }
+
+    fn redraw_controller(&self, id: usize) {
+        let (msg_page_num, id_in_page) = self.controller_to_page_id(id);
+        if self.page_num == msg_page_num {
+            self.update_display(id_in_page, id);
+        }
+    }
+
+    fn update_display(&self, id_in_page: usize, id: usize) {
+        let controller = &self.controllers[id];
+        let state = self.get_display_state(id);
+        let icon = controller.get_icon();
+        let name = match &self.confirm {
+            Some(confirm) if confirm.id == id => "Confirm?".to_string(),
+            _ => controller.get_display_name(),
+        };
+        let extra = controller.get_extra();
+        let message = DisplayCommand::DisplayState(state, icon, id_in_page, name, extra);
+        self.display.send(message);
+    }
+
+    fn update_displays(&self) {
+        self.update_double_presses();
+
+        let range = self.get_controller_range_for_page(self.page_num);
+        for (id_in_page, id) in range.enumerate() {
+            if id < self.controllers.len() {
+                self.update_display(id_in_page, id);
+            } else {
+                let message = DisplayCommand::DisplayNone(id_in_page);
+                self.display.send(message);
+            };
+        }
+    }
+
+    /// Only buttons with a double press bound wait to see if a second press
+    /// follows, which depends on the page shown.
+    fn update_double_presses(&self) {
+        let bound = |id: &usize| matches!(self.config.presses.get(*id), Some(presses) if presses.double_press.is_some());
+
+        let mut ids: HashSet<ButtonId> = (0..self.controllers.len())
+            .filter(bound)
+            .map(ButtonId::Controller)
+            .collect();
+        let range = self.get_controller_range_for_page(self.page_num);
+        ids.extend(
+            range
+                .enumerate()
+                .filter(|(_, id)| bound(id))
+                .map(|(id_in_page, _)| ButtonId::Physical(id_in_page)),
+        );
+
+        self.double_presses.set(ids);
+    }
+
+    fn do_blank(&mut self, force_timer_reset: bool) {
+        let timer_required = self.requested_display_status.get_timer_required();
+        let display_required = self.requested_display_status.get_display_required();
+
+        match (timer_required, self.status.timer_on) {
+            (true, true) => {
+                if force_timer_reset {
+                    info!("resetting blank timer");
+                    self.timer.cancel();
+                    self.timer.start(BLANK_TIMEOUT);
+                    self.status.timer_on = true;
+                }
+            }
+            (true, false) => {
+                info!("starting blank timer");
+                self.timer.cancel();
+                self.timer.start(BLANK_TIMEOUT);
+                self.status.timer_on = true;
+            }
+            (false, true) => {
+                info!("stopping blank timer");
+                self.timer.cancel();
+                self.status.timer_on = false;
+            }
+            (false, false) => {}
+        };
+
+        match (display_required, self.status.display_on) {
+            (true, false) => {
+                info!("turning display on");
+                self.status.display_on = true;
+                self.display.send(DisplayCommand::UnBlankAll);
+                self.publish_status();
+            }
+            (false, true) => {
+                info!("turning display off");
+                self.status.display_on = false;
+                self.display.send(DisplayCommand::BlankAll);
+                self.publish_status();
+            }
+            (true, true) => {}
+            (false, false) => {}
+        };
+    }
+
+    fn has_bindings(&self, id: usize) -> bool {
+        match self.config.presses.get(id) {
+            Some(presses) => presses.has_bindings(),
+            None => false,
+        }
+    }
+
+    /// Buttons with bindings wait for the gesture, the rest have already
+    /// acted on the press.
+    fn gesture_press(&mut self, id: ButtonId, press: Press) {
+        let id = match id {
+            ButtonId::Physical(id_in_page) => {
+                if self.waking_presses.remove(&id_in_page) || !self.status.display_on {
+                    return;
+                }
+                self.page_to_controller_id(self.page_num, id_in_page)
+            }
+            ButtonId::Controller(id) => id,
+            _ => return,
+        };
+
+        if self.has_bindings(id) {
+            self.button_press(id, press);
+        }
+    }
+
+    fn button_press(&mut self, id: usize, press: Press) {
+        info!("Got button {} {:?} press", id, press);
+        let presses = self.config.presses.get(id).cloned().unwrap_or_default();
+        let binding = presses.get_binding(press);
+
+        // A bound gesture doesn't focus. Holding the button is enough to
+        // confirm a long press, but a double press is too easily made by
+        // accident, so an action bound to it still needs confirming.
+        match binding {
+            Some(Binding::Action(_)) if press == Press::Double => {
+                self.unfocus();
+                if !self.confirm_press(id, press) {
+                    return;
+                }
+            }
+            Some(_) => {
+                self.unfocus();
+                self.cancel_confirm();
+            }
+            None => {
+                if !self.focus_press(id) || !self.confirm_press(id, press) {
+                    return;
+                }
+            }
+        }
+
+        let controller_or_none = self.controllers.get_mut(id);
+        if let Some(controller) = controller_or_none {
+            let now = Instant::now();
+            let old_state = controller.get_display_state();
+            let old_extra = controller.get_extra();
+            let (commands, pending) = match binding {
+                Some(binding) => {
+                    let commands = binding.get_commands(controller.as_ref(), presses.qos);
+                    // Only actions go to the controller's own topics.
+                    let pending = matches!(binding, Binding::Action(_));
+                    (commands, pending && controller.get_press_pending())
+                }
+                None => {
+                    controller.process_press(now);
+                    let commands = controller.get_press_commands();
+                    (commands, controller.get_press_pending())
+                }
+            };
+            let changed =
+                old_state != controller.get_display_state() || old_extra != controller.get_extra();
+
+            let topics = commands
+                .iter()
+                .map(|command| command.get_topic().to_string())
+                .collect::<Vec<_>>();
+
+            for command in commands {
+                if command.get_delay().is_zero() {
+                    self.publish_command(&command);
+                } else {
+                    let at = now + command.get_delay();
+                    self.scheduled.push(ScheduledCommand { at, command });
+                }
+            }
+
+            let topic = self.homeassistant.event_topic(id);
+            let event = self.homeassistant.get_press_event(press);
+            self.mqtt.publish(&topic, QoS::AtMostOnce, false, &event);
+
+            if pending && !topics.is_empty() {
+                let deadline = Instant::now() + self.config.get_command_timeout();
+                self.command_status
+                    .insert(id, CommandStatus::Pending { topics, deadline });
+                self.redraw_controller(id);
+            } else if changed {
+                self.redraw_controller(id);
+            }
+
+            self.save_states();
+        } else {
+            error!("Controller for button {} does not exist", id);
+        }
+    }
+
+    /// Move the focus to the pressed button if it is adjustable. Returns
+    /// false if the press only focused the button.
+    fn focus_press(&mut self, id: usize) -> bool {
+        let deadline = Instant::now() + FOCUS_TIMEOUT;
+
+        if let Some(focus) = &mut self.focus {
+            if focus.id == id {
+                focus.deadline = deadline;
+                return true;
+            }
+        }
+        self.unfocus();
+
+        match self.controllers.get(id) {
+            Some(controller) if controller.get_adjustable() => {
+                info!("Focusing button {}", id);
+                self.focus = Some(Focus { id, deadline });
+                let (msg_page_num, id_in_page) = self.controller_to_page_id(id);
+                if msg_page_num == self.page_num {
+                    self.display.send(DisplayCommand::ButtonFocused(id_in_page));
+                }
+                false
+            }
+            _ => true,
+        }
+    }
+
+    fn unfocus(&mut self) {
+        if let Some(focus) = self.focus.take() {
+            info!("Unfocusing button {}", focus.id);
+            let (msg_page_num, id_in_page) = self.controller_to_page_id(focus.id);
+            if msg_page_num == self.page_num {
+                self.display
+                    .send(DisplayCommand::ButtonUnfocused(id_in_page));
+            }
+        }
+    }
+
+    /// Ask for confirmation if the controller wants it, or take the press
+    /// as the confirmation. Returns false if the press only asked.
+    fn confirm_press(&mut self, id: usize, press: Press) -> bool {
+        if matches!(&self.confirm, Some(confirm) if confirm.id == id && confirm.press == press) {
+            info!("Button {} press confirmed", id);
+            self.confirm = None;
+            return true;
+        }
+        self.cancel_confirm();
+
+        let timeout = match self.controllers.get(id) {
+            Some(controller) => controller.get_press_confirm(),
+            None => None,
+        };
+
+        match timeout {
+            Some(timeout) => {
+                info!("Button {} press needs confirming", id);
+                let deadline = Instant::now() + timeout;
+                self.confirm = Some(Confirm {
+                    id,
+                    press,
+                    deadline,
+                });
+                self.redraw_controller(id);
+                false
+            }
+            None => true,
+        }
+    }
+
+    fn cancel_confirm(&mut self) {
+        if let Some(confirm) = self.confirm.take() {
+            info!("Button {} press not confirmed", confirm.id);
+            self.redraw_controller(confirm.id);
+        }
+    }
+
+    fn adjust_focused(&mut self, up: bool) {
+        let id = match &mut self.focus {
+            Some(focus) => {
+                focus.deadline = Instant::now() + FOCUS_TIMEOUT;
+                focus.id
+            }
+            None => return,
+        };
+
+        let commands = match self.controllers.get_mut(id) {
+            Some(controller) => controller.adjust(up),
+            None => return,
+        };
+        for command in &commands {
+            self.publish_command(command);
+        }
+        self.redraw_controller(id);
+    }
+
+    fn publish_command(&self, command: &Command) {
+        let topic = command.get_topic();
+        let data = command.get_message();
+        info!("Send {}: {}", topic, data);
+        self.mqtt
+            .publish(topic, command.get_qos(), command.get_retain(), &data);
+    }
+
+    /// Send the delayed commands that are due, in the order they were due.
+    /// They wait while MQTT is down.
+    fn publish_scheduled(&mut self, now: Instant) {
+        if !self.mqtt_connected {
+            return;
+        }
+
+        let (mut due, scheduled) = std::mem::take(&mut self.scheduled)
+            .into_iter()
+            .partition::<Vec<_>, _>(|scheduled| scheduled.at <= now);
+        self.scheduled = scheduled;
+
+        due.sort_by_key(|scheduled| scheduled.at);
+        for scheduled in due {
+            self.publish_command(&scheduled.command);
+        }
+    }
+
+    /// Let controllers that keep time move on, and schedule anything that
+    /// came due.
+    fn tick_controllers(&mut self, now: Instant) {
+        for id in 0..self.controllers.len() {
+            let controller = &mut self.controllers[id];
+            let old_state = controller.get_display_state();
+            let old_extra = controller.get_extra();
+            let commands = controller.process_tick(now);
+            let changed =
+                old_state != controller.get_display_state() || old_extra != controller.get_extra();
+
+            for command in commands {
+                let at = now + command.get_delay();
+                self.scheduled.push(ScheduledCommand { at, command });
+            }
+
+            if changed {
+                self.redraw_controller(id);
+            }
+        }
+    }
+
+    fn restore_states(&mut self) {
+        for (id, controller) in self.controllers.iter_mut().enumerate() {
+            if controller.get_saved_state().is_none() {
+                continue;
+            }
+
+            match self.storage.load_state(&state_key(&controller.get_name())) {
+                Ok(Some(state)) => {
+                    controller.restore_state(&state);
+                    self.saved_states.insert(id, state);
+                }
+                Ok(None) => {}
+                Err(err) => error!("Cannot load state for controller {}: {}", id, err),
+            }
+        }
+    }
+
+    fn save_states(&mut self) {
+        for (id, controller) in self.controllers.iter().enumerate() {
+            let state = match controller.get_saved_state() {
+                Some(state) => state,
+                None => continue,
+            };
+
+            if self.saved_states.get(&id) == Some(&state) {
+                continue;
+            }
+
+            let key = state_key(&controller.get_name());
+            match self.storage.save_state(&key, &state) {
+                Ok(()) => {
+                    self.saved_states.insert(id, state);
+                }
+                Err(err) => error!("Cannot save state for controller {}: {}", id, err),
+            }
+        }
+    }
+
+    fn get_controller_range_for_page(&self, page: usize) -> Range<usize> {
+        let start = page * self.num_per_page;
+        let end = start + self.num_per_page;
+        Range { start, end }
+    }
+
+    fn controller_to_page_id(&self, controller_id: usize) -> (usize, usize) {
+        let page_num = controller_id / self.num_per_page;
+        let id_in_page = controller_id % self.num_per_page;
+        (page_num, id_in_page)
+    }
+
+    fn page_to_controller_id(&self, page_num: usize, id_in_page: usize) -> usize {
+        page_num * self.num_per_page + id_in_page
+    }
+
+    fn get_last_page(&self) -> usize {
+        let len = self.controllers.len();
+        let num = self.num_per_page;
+        let num_pages = len / num + usize::from(len % num != 0);
+        num_pages.saturating_sub(1)
+    }
+}
+
+/// Saved state is keyed by the controller's name rather than its place in
+/// the config, so that it survives controllers being added or moved. NVS
+/// keys are at most 15 characters, so the name is hashed with FNV-1a, which
+/// unlike the std hasher is the same on every build.
+fn state_key(name: &str) -> String {
+    let hash = name.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    });
+    format!("state{:08x}", hash)
+}
+
+fn create_controllers(config: &config::RemoteConfig) -> Vec<Box<dyn Controller>> {
+    config
+        .controllers
+        .iter()
+        .map(|x| x.create_controller())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::button_controllers::{Extra, Icon};
+
+    #[derive(Default)]
+    struct FakeMqtt {
+        subscribed: RefCell<Vec<String>>,
+        published: RefCell<Vec<(String, String)>>,
+        offline: RefCell<bool>,
+    }
+
+    impl MqttOutput for FakeMqtt {
+        fn subscribe(&self, topic: &str, _label: Label, _qos: QoS) {
+            self.subscribed.borrow_mut().push(topic.to_string());
+        }
+
+        fn unsubscribe(&self, topic: &str, _label: Label) {
+            self.subscribed.borrow_mut().retain(|t| t != topic);
+        }
+
+        fn publish(&self, topic: &str, _qos: QoS, _retain: bool, data: &str) {
+            self.published
+                .borrow_mut()
+                .push((topic.to_string(), data.to_string()));
+        }
+
+        fn offline(&self) {
+            *self.offline.borrow_mut() = true;
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeDisplay {
+        commands: RefCell<Vec<DisplayCommand>>,
+    }
+
+    impl DisplayOutput for FakeDisplay {
+        fn send(&self, command: DisplayCommand) {
+            self.commands.borrow_mut().push(command);
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum TimerEvent {
+        Start(Duration),
+        Cancel,
+    }
+
+    #[derive(Default)]
+    struct FakeTimer {
+        events: Vec<TimerEvent>,
+    }
+
+    impl Timer for FakeTimer {
+        fn start(&mut self, duration: Duration) {
+            self.events.push(TimerEvent::Start(duration));
+        }
+
+        fn cancel(&mut self) {
+            self.events.push(TimerEvent::Cancel);
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeStore {
+        config: Option<String>,
+        states: HashMap<String, String>,
+    }
+
+    impl ConfigStore for FakeStore {
+        fn save(&mut self, config: &str) -> anyhow::Result<()> {
+            self.config = Some(config.to_string());
+            Ok(())
+        }
+
+        fn save_state(&mut self, key: &str, state: &str) -> anyhow::Result<()> {
+            self.states.insert(key.to_string(), state.to_string());
+            Ok(())
+        }
+
+        fn load_state(&self, key: &str) -> anyhow::Result<Option<String>> {
+            Ok(self.states.get(key).cloned())
+        }
+    }
+
+    type TestApp = App<FakeMqtt, FakeDisplay, FakeTimer, FakeStore>;
+
+    fn switch(name: &str) -> String {
+        format!(
+            r#"{{"type": "switch", "name": "{}", "topic_substr": "{}", "action": "toggle", "icon": "fan"}}"#,
+            name, name
+        )
+    }
+
+    fn new_app(json: &str) -> TestApp {
+        let config = config::parse_config(json).unwrap();
+        let mut app = App::new(
+            FakeMqtt::default(),
+            FakeDisplay::default(),
+            FakeTimer::default(),
+            FakeStore::default(),
+            DoublePresses::default(),
+            4,
+            "test".to_string(),
+            json.to_string(),
+            config,
+        );
+        app.start();
+        app.process_message(Message::MqttConnect);
+        take_display(&app);
+        take_published(&app);
+        app
+    }
+
+    fn switches(names: &[&str]) -> TestApp {
+        let controllers: Vec<String> = names.iter().map(|name| switch(name)).collect();
+        new_app(&format!(
+            r#"{{"controllers": [{}]}}"#,
+            controllers.join(",")
+        ))
+    }
+
+    fn take_display(app: &TestApp) -> Vec<DisplayCommand> {
+        app.display.commands.borrow_mut().drain(..).collect()
+    }
+
+    /// Everything published, apart from the Home Assistant discovery.
+    fn take_published(app: &TestApp) -> Vec<(String, String)> {
+        app.mqtt
+            .published
+            .borrow_mut()
+            .drain(..)
+            .filter(|(topic, _)| !topic.starts_with("homeassistant/"))
+            .collect()
+    }
+
+    fn published(topic: &str, data: &str) -> (String, String) {
+        (topic.to_string(), data.to_string())
+    }
+
+    fn tile(state: DisplayState, id_in_page: usize, name: &str) -> DisplayCommand {
+        DisplayCommand::DisplayState(state, Icon::Fan, id_in_page, name.to_string(), Extra::None)
+    }
+
+    fn power(id: usize, data: &str) -> Message {
+        Message::MqttReceived(
+            "state/power".to_string(),
+            data.as_bytes().to_vec(),
+            Label::Button(id, 0),
+        )
+    }
+
+    fn night(data: &str) -> Message {
+        Message::MqttReceived(
+            "night".to_string(),
+            data.as_bytes().to_vec(),
+            Label::NightStatus,
+        )
+    }
+
+    #[test]
+    fn start_draws_the_first_page() {
+        let config = config::parse_config(&format!(r#"{{"controllers": [{}]}}"#, switch("Fan")));
+        let mut app: TestApp = App::new(
+            FakeMqtt::default(),
+            FakeDisplay::default(),
+            FakeTimer::default(),
+            FakeStore::default(),
+            DoublePresses::default(),
+            4,
+            "test".to_string(),
+            String::new(),
+            config.unwrap(),
+        );
+        app.start();
+
+        assert_eq!(
+            take_display(&app),
+            vec![
+                DisplayCommand::Started,
+                DisplayCommand::ShowPage(0),
+                tile(DisplayState::Unknown, 0, "Fan"),
+                DisplayCommand::DisplayNone(1),
+                DisplayCommand::DisplayNone(2),
+                DisplayCommand::DisplayNone(3),
+            ]
+        );
+        assert_eq!(*app.mqtt.subscribed.borrow(), vec!["state/Fan/power"]);
+    }
+
+    #[test]
+    fn press_sends_command_and_shows_pending() {
+        let mut app = switches(&["Fan"]);
+        app.process_message(power(0, "OFF"));
+        assert_eq!(take_display(&app), vec![tile(DisplayState::Off, 0, "Fan")]);
+
+        app.process_message(Message::ButtonPress(ButtonId::Physical(0)));
+        assert_eq!(
+            take_published(&app),
+            vec![
+                published("command/Fan", r#"{"action":"turn_on"}"#),
+                published("state/remote/test/button/0", r#"{"event_type":"press"}"#),
+            ]
+        );
+        assert_eq!(
+            take_display(&app),
+            vec![
+                tile(DisplayState::Pending, 0, "Fan"),
+                DisplayCommand::ButtonPressed(0),
+            ]
+        );
+
+        app.process_message(power(0, "ON"));
+        assert_eq!(take_display(&app), vec![tile(DisplayState::On, 0, "Fan")]);
+    }
+
+    #[test]
+    fn pending_command_times_out() {
+        let mut app = new_app(&format!(
+            r#"{{"command_timeout": 0, "controllers": [{}]}}"#,
+            switch("Fan")
+        ));
+        app.process_message(power(0, "OFF"));
+        app.process_message(Message::ButtonPress(ButtonId::Physical(0)));
+        take_display(&app);
+
+        app.process_message(Message::Tick);
+        assert_eq!(
+            take_display(&app),
+            vec![tile(DisplayState::Error, 0, "Fan")]
+        );
+    }
+
+    #[test]
+    fn paging() {
+        let mut app = switches(&["A", "B", "C", "D", "E"]);
+
+        app.process_message(Message::ButtonPress(ButtonId::PageUp));
+        assert_eq!(
+            take_display(&app),
+            vec![
+                DisplayCommand::ShowPage(1),
+                tile(DisplayState::Unknown, 0, "E"),
+                DisplayCommand::DisplayNone(1),
+                DisplayCommand::DisplayNone(2),
+                DisplayCommand::DisplayNone(3),
+            ]
+        );
+        assert!(take_published(&app).contains(&published("state/remote/test/page", "1")));
+
+        // There is no page 2.
+        app.process_message(Message::ButtonPress(ButtonId::PageUp));
+        assert_eq!(take_display(&app)[0], DisplayCommand::ShowPage(1));
+        take_published(&app);
+
+        // Buttons act on the controllers of the page shown.
+        app.process_message(Message::ButtonPress(ButtonId::Physical(0)));
+        assert_eq!(
+            take_published(&app)[0],
+            published("command/E", r#"{"action":"turn_on"}"#)
+        );
+        take_display(&app);
+
+        app.process_message(Message::ButtonPress(ButtonId::PageDown));
+        let commands = take_display(&app);
+        assert_eq!(commands[0], DisplayCommand::ShowPage(0));
+        assert_eq!(commands[1], tile(DisplayState::Unknown, 0, "A"));
+    }
+
+    #[test]
+    fn night_blanks_until_a_button_is_pressed() {
+        let mut app = switches(&["Fan"]);
+
+        app.process_message(night("ON"));
+        assert_eq!(take_display(&app), vec![DisplayCommand::BlankAll]);
+        assert!(take_published(&app).contains(&published("state/remote/test/display", "OFF")));
+
+        // The press that wakes the display doesn't do anything else.
+        app.process_message(Message::ButtonPress(ButtonId::Physical(0)));
+        assert_eq!(take_display(&app), vec![DisplayCommand::UnBlankAll]);
+        let publishes = take_published(&app);
+        assert!(publishes.contains(&published("state/remote/test/display", "ON")));
+        assert!(!publishes
+            .iter()
+            .any(|(topic, _)| topic.starts_with("command/")));
+        assert_eq!(
+            app.timer.events.drain(..).collect::<Vec<_>>(),
+            vec![TimerEvent::Cancel, TimerEvent::Start(BLANK_TIMEOUT)]
+        );
+
+        app.process_message(Message::BlankDisplays);
+        assert_eq!(take_display(&app), vec![DisplayCommand::BlankAll]);
+        assert_eq!(
+            app.timer.events.drain(..).collect::<Vec<_>>(),
+            vec![TimerEvent::Cancel]
+        );
+
+        app.process_message(night("OFF"));
+        assert_eq!(take_display(&app), vec![DisplayCommand::UnBlankAll]);
+    }
+
+    #[test]
+    fn night_controller_keeps_the_display_on() {
+        let mut app = new_app(&format!(
+            r#"{{"night_controller": 0, "controllers": [{}]}}"#,
+            switch("Fan")
+        ));
+        app.process_message(power(0, "ON"));
+        take_display(&app);
+
+        app.process_message(night("ON"));
+        assert_eq!(take_display(&app), vec![]);
+
+        app.process_message(power(0, "OFF"));
+        assert_eq!(
+            take_display(&app),
+            vec![DisplayCommand::BlankAll, tile(DisplayState::Off, 0, "Fan")]
+        );
+    }
+
+    #[test]
+    fn invalid_message_is_an_error_until_that_topic_is_valid() {
+        let mut app = new_app(
+            r#"{"controllers": [{"type": "light", "name": "Lamp", "topic_substr": "Lamp",
+                "action": "toggle", "icon": "light", "scene": "default", "priority": 100}]}"#,
+        );
+        let scenes = |data: &[u8]| {
+            Message::MqttReceived(
+                "state/Lamp/scenes".to_string(),
+                data.to_vec(),
+                Label::Button(0, 1),
+            )
+        };
+        let lamp = |state| {
+            DisplayCommand::DisplayState(state, Icon::Light, 0, "Lamp".to_string(), Extra::None)
+        };
+        take_display(&app);
+
+        app.process_message(scenes(&[0xff]));
+        assert_eq!(take_display(&app), vec![lamp(DisplayState::Error)]);
+
+        app.process_message(power(0, "ON"));
+        assert_eq!(app.get_display_state(0), DisplayState::Error);
+
+        app.process_message(scenes(br#"["default"]"#));
+        assert_eq!(take_display(&app), vec![lamp(DisplayState::On)]);
+
+        app.process_message(scenes(&[0xff]));
+        app.process_message(Message::MqttDisconnect);
+        assert_eq!(app.get_display_state(0), DisplayState::Unknown);
+    }
+
+    #[test]
+    fn new_config_drops_scheduled_steps() {
+        let json = r#"{"controllers": [{"type": "macro", "name": "Bedtime", "icon": "wake_up",
+            "steps": [{"topic": "lights", "message": "OFF"},
+                      {"topic": "fan", "message": "OFF", "delay": 60}]}]}"#;
+        let mut app = new_app(json);
+        app.process_message(Message::ButtonPress(ButtonId::Physical(0)));
+        assert!(take_published(&app).contains(&published("lights", "OFF")));
+        assert_eq!(app.scheduled.len(), 1);
+
+        let json = format!(r#"{{"controllers": [{}]}}"#, switch("Fan"));
+        app.process_message(Message::MqttReceived(
+            "config".to_string(),
+            json.into_bytes(),
+            Label::Config,
+        ));
+        assert!(app.scheduled.is_empty());
+    }
+
+    #[test]
+    fn saved_state_follows_the_controller_name() {
+        let countdown = |name: &str| {
+            format!(
+                r#"{{"type": "countdown", "name": "{}", "icon": "light", "increment": 60,
+                    "persist": true, "command": {{"topic": "kettle", "message": "off"}}}}"#,
+                name
+            )
+        };
+        let mut app = new_app(&format!(r#"{{"controllers": [{}]}}"#, countdown("Tea")));
+        app.process_message(Message::ButtonPress(ButtonId::Physical(0)));
+        app.process_message(Message::Tick);
+        assert!(app.storage.states.contains_key(&state_key("Tea")));
+
+        let json = format!(
+            r#"{{"controllers": [{}, {}, {}]}}"#,
+            countdown("Eggs"),
+            switch("Fan"),
+            countdown("Tea")
+        );
+        app.process_message(Message::MqttReceived(
+            "config".to_string(),
+            json.into_bytes(),
+            Label::Config,
+        ));
+        assert_eq!(app.get_display_state(0), DisplayState::Off);
+        assert_eq!(app.get_display_state(2), DisplayState::On);
+    }
+
+    #[test]
+    fn restart_needs_an_explicit_payload() {
+        let mut app = switches(&["Fan"]);
+        for data in ["", "ON", "restart\n"] {
+            app.process_message(Message::MqttReceived(
+                "restart".to_string(),
+                data.as_bytes().to_vec(),
+                Label::Restart,
+            ));
+            assert!(!app.restart_required());
+        }
+
+        app.process_message(Message::MqttReceived(
+            "restart".to_string(),
+            b"restart".to_vec(),
+            Label::Restart,
+        ));
+        assert!(app.restart_required());
+        assert!(*app.mqtt.offline.borrow());
+    }
+
+    fn lock(data: &str) -> Message {
+        Message::MqttReceived(
+            "state/Door/state".to_string(),
+            data.as_bytes().to_vec(),
+            Label::Button(0, 0),
+        )
+    }
+
+    #[test]
+    fn double_press_unlock_needs_confirming() {
+        let mut app = new_app(
+            r#"{"controllers": [{"type": "lock", "name": "Door", "topic_substr": "Door",
+                "icon": "light", "double_press": "toggle"}]}"#,
+        );
+        app.process_message(lock("locked"));
+
+        let unlock = published("command/Door", r#"{"action":"unlock"}"#);
+        app.process_message(Message::DoublePress(ButtonId::Physical(0)));
+        assert!(!take_published(&app).contains(&unlock));
+
+        // A short press doesn't confirm a double press.
+        app.process_message(Message::ShortPress(ButtonId::Physical(0)));
+        assert!(!take_published(&app).contains(&unlock));
+
+        app.process_message(Message::DoublePress(ButtonId::Physical(0)));
+        app.process_message(Message::DoublePress(ButtonId::Physical(0)));
+        assert!(take_published(&app).contains(&unlock));
+    }
+
+    #[test]
+    fn long_press_unlock_is_confirmed() {
+        let mut app = new_app(
+            r#"{"controllers": [{"type": "lock", "name": "Door", "topic_substr": "Door",
+                "icon": "light", "long_press": "toggle"}]}"#,
+        );
+        app.process_message(lock("locked"));
+
+        app.process_message(Message::LongPress(ButtonId::Physical(0)));
+        assert!(take_published(&app).contains(&published("command/Door", r#"{"action":"unlock"}"#)));
+    }
+
+    #[test]
+    fn unchanged_state_ends_pending() {
+        let mut app = switches(&["Fan"]);
+        app.process_message(power(0, "OFF"));
+        app.process_message(Message::ButtonPress(ButtonId::Physical(0)));
+        take_display(&app);
+
+        // Someone else turned it off again, or the command was ignored.
+        app.process_message(power(0, "OFF"));
+        assert_eq!(take_display(&app), vec![tile(DisplayState::Off, 0, "Fan")]);
+    }
+
+    #[test]
+    fn night_publishes_status_once() {
+        let mut app = switches(&["Fan"]);
+        let night_on = published("state/remote/test/night", "ON");
+
+        // The display blanks.
+        app.process_message(night("ON"));
+        let publishes = take_published(&app);
+        assert_eq!(publishes.iter().filter(|p| **p == night_on).count(), 1);
+
+        // The display stays off.
+        app.process_message(night("ON"));
+        let publishes = take_published(&app);
+        assert_eq!(publishes.iter().filter(|p| **p == night_on).count(), 1);
+    }
+
+    #[test]
+    fn only_double_press_bindings_wait() {
+        let controllers: Vec<String> = ["A", "B", "C", "D", "E"]
+            .iter()
+            .map(|name| switch(name))
+            .collect();
+        let mut controllers = controllers;
+        controllers[0] = controllers[0].replace('}', r#", "long_press": "turn_off"}"#);
+        controllers[4] = controllers[4].replace('}', r#", "double_press": "turn_off"}"#);
+        let mut app = new_app(&format!(
+            r#"{{"controllers": [{}]}}"#,
+            controllers.join(",")
+        ));
+        let double_presses = app.double_presses.clone();
+
+        assert!(double_presses.contains(ButtonId::Controller(4)));
+        assert!(!double_presses.contains(ButtonId::Controller(0)));
+        assert!(!double_presses.contains(ButtonId::Physical(0)));
+
+        app.process_message(Message::ButtonPress(ButtonId::PageUp));
+        assert!(double_presses.contains(ButtonId::Physical(0)));
+        assert!(double_presses.contains(ButtonId::Controller(4)));
+    }
+
+    #[test]
+    fn confirming_shows_pending() {
+        let mut app = new_app(
+            r#"{"controllers": [{"type": "lock", "name": "Door", "topic_substr": "Door",
+                "icon": "light"}]}"#,
+        );
+        app.process_message(lock("locked"));
+        take_display(&app);
+
+        let confirm = DisplayCommand::DisplayState(
+            DisplayState::Pending,
+            Icon::Light,
+            0,
+            "Confirm?".to_string(),
+            Extra::None,
+        );
+        app.process_message(Message::ButtonPress(ButtonId::Physical(0)));
+        assert!(take_display(&app).contains(&confirm));
+    }
+
+    #[test]
+    fn toggle_binding_on_a_turn_off_tile() {
+        let mut app = new_app(
+            r#"{"controllers": [{"type": "switch", "name": "Fan", "topic_substr": "Fan",
+                "action": "turn_off", "icon": "fan", "long_press": "toggle"}]}"#,
+        );
+
+        // On shows as off on a turn_off tile, but toggling turns it off.
+        app.process_message(power(0, "ON"));
+        assert_eq!(take_display(&app), vec![tile(DisplayState::Off, 0, "Fan")]);
+        app.process_message(Message::LongPress(ButtonId::Physical(0)));
+        assert_eq!(
+            take_published(&app)[0],
+            published("command/Fan", r#"{"action":"turn_off"}"#)
+        );
+
+        app.process_message(power(0, "OFF"));
+        app.process_message(Message::LongPress(ButtonId::Physical(0)));
+        assert_eq!(
+            take_published(&app)[0],
+            published("command/Fan", r#"{"action":"turn_on"}"#)
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use std::env;
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+use anyhow::Result;
+
+use crate::button;
+use crate::display;
+use crate::messages;
+use crate::storage;
+
+use super::Board;
+
+pub const NUM_CONTROLLERS_PER_PAGE: usize = display::host::NUM_PER_PAGE;
+
+pub struct HostBoard {
+    display: mpsc::Sender<display::DisplayCommand>,
+    config_path: PathBuf,
+}
+
+impl Board for HostBoard {
+    fn get_display(&self) -> mpsc::Sender<display::DisplayCommand> {
+        self.display.clone()
+    }
+
+    fn get_config_storage(&self) -> Result<storage::ConfigStorage> {
+        storage::ConfigStorage::new(self.config_path.clone())
+    }
+}
+
+pub fn configure_devices(tx: mpsc::Sender<messages::Message>) -> Result<HostBoard> {
+    let display = display::host::connect()?;
+
+    button::stdin::configure_stdin(tx);
+
+    let config_path = env::var("HOST_CONFIG").unwrap_or_else(|_| "host-config.json".to_string());
+
+    Ok(HostBoard {
+        display,
+        config_path: PathBuf::from(config_path),
+    })
+}
@@ -1,9 +1,11 @@
 use std::sync::mpsc;
+use std::sync::Arc;
 
 use anyhow::Result;
 
 use esp_idf_hal::prelude::Peripherals;
 
+use esp_idf_svc::nvs::EspDefaultNvs;
 use esp_idf_svc::sntp::EspSntp;
 use esp_idf_svc::wifi::EspWifi;
 
@@ -11,6 +13,7 @@ use crate::button;
 use crate::display;
 use crate::input::esp32::TouchControllerBuilder;
 use crate::messages;
+use crate::storage;
 use crate::wifi;
 
 use super::Board;
@@ -21,6 +24,7 @@ pub const NUM_CONTROLLERS_PER_PAGE: usize = display::lca2021_badge::NUM_PER_PAGE
 pub struct Lca2022Badge {
     wifi: EspWifi,
     sntp: EspSntp,
+    default_nvs: Arc<EspDefaultNvs>,
     display: mpsc::Sender<display::DisplayCommand>,
 }
 
@@ -28,6 +32,10 @@ impl Board for Lca2022Badge {
     fn get_display(&self) -> mpsc::Sender<display::DisplayCommand> {
         self.display.clone()
     }
+
+    fn get_config_storage(&self) -> Result<storage::ConfigStorage> {
+        storage::ConfigStorage::new(self.default_nvs.clone())
+    }
 }
 
 pub fn configure_devices(tx: mpsc::Sender<messages::Message>) -> Result<Lca2022Badge> {
@@ -36,7 +44,7 @@ pub fn configure_devices(tx: mpsc::Sender<messages::Message>) -> Result<Lca2022B
 
     let display = display::lca2021_badge::connect(peripherals.i2c0, pins.gpio4, pins.gpio5)?;
 
-    let (wifi, sntp) = wifi::esp::connect()?;
+    let (wifi, sntp, default_nvs) = wifi::esp::connect()?;
 
     let pin = pins.gpio16.into_input().unwrap();
     button::gpio::configure_button(pin, tx.clone(), button::ButtonId::Physical(0))?;
@@ -58,6 +66,7 @@ pub fn configure_devices(tx: mpsc::Sender<messages::Message>) -> Result<Lca2022B
     Ok(Lca2022Badge {
         wifi,
         sntp,
+        default_nvs,
         display,
     })
 }
@@ -1,12 +1,14 @@
 mod touchscreen;
 
 use std::sync::mpsc;
+use std::sync::Arc;
 
 use anyhow::Result;
 
 use embedded_graphics::prelude::*;
 use embedded_graphics::primitives::Rectangle;
 use esp_idf_hal::prelude::*;
+use esp_idf_svc::nvs::EspDefaultNvs;
 use esp_idf_svc::sntp::EspSntp;
 use esp_idf_svc::wifi::EspWifi;
 
@@ -14,6 +16,7 @@ use crate::button::ButtonId;
 use crate::display;
 use crate::display::makerfab::NUM_PER_PAGE;
 use crate::messages;
+use crate::storage;
 use crate::wifi;
 
 use super::Board;
@@ -24,6 +27,7 @@ pub const NUM_CONTROLLERS_PER_PAGE: usize = display::makerfab::NUM_PER_PAGE;
 pub struct Makerfab {
     wifi: EspWifi,
     sntp: EspSntp,
+    default_nvs: Arc<EspDefaultNvs>,
     display: mpsc::Sender<display::DisplayCommand>,
     // touch_screen: Ft6x36<EspI2c1>,
 }
@@ -32,6 +36,10 @@ impl Board for Makerfab {
     fn get_display(&self) -> mpsc::Sender<display::DisplayCommand> {
         self.display.clone()
     }
+
+    fn get_config_storage(&self) -> Result<storage::ConfigStorage> {
+        storage::ConfigStorage::new(self.default_nvs.clone())
+    }
 }
 
 pub struct ButtonInfo {
@@ -110,7 +118,7 @@ pub fn configure_devices(tx: mpsc::Sender<messages::Message>) -> Result<Makerfab
     )
     .unwrap();
 
-    let (wifi, sntp) = wifi::esp::connect()?;
+    let (wifi, sntp, default_nvs) = wifi::esp::connect()?;
 
     let sda = pins.gpio26.into_output().unwrap();
     let scl = pins.gpio27.into_output().unwrap();
@@ -120,6 +128,7 @@ pub fn configure_devices(tx: mpsc::Sender<messages::Message>) -> Result<Makerfab
     Ok(Makerfab {
         wifi,
         sntp,
+        default_nvs,
         display,
     })
 }
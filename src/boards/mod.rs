@@ -2,8 +2,10 @@ use std::sync::mpsc;
 
 use anyhow::Result;
 
+use crate::button;
 use crate::display;
 use crate::messages;
+use crate::storage;
 
 #[cfg(feature = "lca2021_badge")]
 pub mod lca2021_badge;
@@ -23,12 +25,22 @@ pub mod makerfab;
 #[cfg(feature = "makerfab")]
 use makerfab as board;
 
+#[cfg(feature = "host")]
+pub mod host;
+
+#[cfg(feature = "host")]
+use host as board;
+
 pub const NUM_CONTROLLERS_PER_PAGE: usize = board::NUM_CONTROLLERS_PER_PAGE;
 
 pub trait Board {
     fn get_display(&self) -> mpsc::Sender<display::DisplayCommand>;
+    fn get_config_storage(&self) -> Result<storage::ConfigStorage>;
 }
 
-pub fn configure_devices(tx: mpsc::Sender<messages::Message>) -> Result<impl Board> {
-    board::configure_devices(tx)
+pub fn configure_devices(
+    tx: mpsc::Sender<messages::Message>,
+    double_presses: button::gestures::DoublePresses,
+) -> Result<impl Board> {
+    board::configure_devices(button::gestures::connect(tx, double_presses))
 }
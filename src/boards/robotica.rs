@@ -1,15 +1,18 @@
 use std::sync::mpsc;
+use std::sync::Arc;
 
 use anyhow::Result;
 
 use esp_idf_hal::prelude::Peripherals;
 
+use esp_idf_svc::nvs::EspDefaultNvs;
 use esp_idf_svc::sntp::EspSntp;
 use esp_idf_svc::wifi::EspWifi;
 
 use crate::button;
 use crate::display;
 use crate::messages;
+use crate::storage;
 use crate::wifi;
 
 use super::Board;
@@ -20,6 +23,7 @@ pub const NUM_CONTROLLERS_PER_PAGE: usize = 4;
 pub struct RoboticaBoard {
     wifi: EspWifi,
     sntp: EspSntp,
+    default_nvs: Arc<EspDefaultNvs>,
     display: mpsc::Sender<display::DisplayCommand>,
 }
 
@@ -27,6 +31,10 @@ impl Board for RoboticaBoard {
     fn get_display(&self) -> mpsc::Sender<display::DisplayCommand> {
         self.display.clone()
     }
+
+    fn get_config_storage(&self) -> Result<storage::ConfigStorage> {
+        storage::ConfigStorage::new(self.default_nvs.clone())
+    }
 }
 
 pub fn configure_devices(tx: mpsc::Sender<messages::Message>) -> Result<RoboticaBoard> {
@@ -47,11 +55,12 @@ pub fn configure_devices(tx: mpsc::Sender<messages::Message>) -> Result<Robotica
 
     let display = display::robotica::connect(13)?;
 
-    let (wifi, sntp) = wifi::esp::connect()?;
+    let (wifi, sntp, default_nvs) = wifi::esp::connect()?;
 
     Ok(RoboticaBoard {
         wifi,
         sntp,
+        default_nvs,
         display,
     })
 }
@@ -0,0 +1,334 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::messages;
+use crate::messages::Message;
+
+use super::ButtonId;
+
+/// Held at least this long is a long press.
+pub const LONG_PRESS: Duration = Duration::from_millis(800);
+
+/// A second press this soon after a release is a double press.
+pub const DOUBLE_PRESS: Duration = Duration::from_millis(300);
+
+/// The buttons that have a double press bound, kept up to date by the app as
+/// the page and config change. Other buttons don't wait to see if a second
+/// press follows, so two quick presses are two short presses.
+#[derive(Clone, Default)]
+pub struct DoublePresses(Arc<Mutex<HashSet<ButtonId>>>);
+
+impl DoublePresses {
+    pub fn set(&self, ids: HashSet<ButtonId>) {
+        *self.0.lock().unwrap() = ids;
+    }
+
+    pub fn contains(&self, id: ButtonId) -> bool {
+        self.0.lock().unwrap().contains(&id)
+    }
+}
+
+enum State {
+    /// Down since then. `done` once it has turned into a long or double
+    /// press, so the release is just a release.
+    Down { since: Instant, done: bool },
+    /// Released after a short press, waiting to see if a second one follows.
+    Up { since: Instant },
+}
+
+struct Recognizer {
+    double_presses: DoublePresses,
+    states: HashMap<ButtonId, State>,
+}
+
+impl Recognizer {
+    fn new(double_presses: DoublePresses) -> Self {
+        Recognizer {
+            double_presses,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Handle a message, or `None` if the deadline passed, and return what
+    /// to pass on.
+    fn process(&mut self, received: Option<Message>, now: Instant) -> Vec<Message> {
+        let mut output = Vec::new();
+
+        match received {
+            Some(Message::ButtonPress(id)) if has_gestures(id) => {
+                output.push(Message::ButtonPress(id));
+                let done = match self.states.get(&id) {
+                    Some(State::Up { since }) if now - *since < DOUBLE_PRESS => {
+                        output.push(Message::DoublePress(id));
+                        true
+                    }
+                    _ => false,
+                };
+                self.states.insert(id, State::Down { since: now, done });
+            }
+            Some(Message::ButtonRelease(id)) if has_gestures(id) => {
+                match self.states.remove(&id) {
+                    // Released before the long press deadline was handled.
+                    Some(State::Down { since, done: false }) if now - since >= LONG_PRESS => {
+                        output.push(Message::LongPress(id));
+                        output.push(Message::ButtonRelease(id));
+                    }
+                    Some(State::Down { done: false, .. }) => {
+                        output.push(Message::ButtonRelease(id));
+                        if self.double_presses.contains(id) {
+                            self.states.insert(id, State::Up { since: now });
+                        } else {
+                            output.push(Message::ShortPress(id));
+                        }
+                    }
+                    _ => output.push(Message::ButtonRelease(id)),
+                }
+            }
+            Some(message) => output.push(message),
+            None => {}
+        }
+
+        for (id, state) in self.states.iter_mut() {
+            match state {
+                State::Down { since, done } if !*done && now - *since >= LONG_PRESS => {
+                    output.push(Message::LongPress(*id));
+                    *done = true;
+                }
+                State::Up { since } if now - *since >= DOUBLE_PRESS => {
+                    output.push(Message::ShortPress(*id));
+                }
+                _ => {}
+            }
+        }
+        self.states.retain(|_, state| match state {
+            State::Up { since } => now - *since < DOUBLE_PRESS,
+            State::Down { .. } => true,
+        });
+
+        output
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.states
+            .values()
+            .filter_map(|state| match state {
+                State::Down { since, done: false } => Some(*since + LONG_PRESS),
+                State::Down { done: true, .. } => None,
+                State::Up { since } => Some(*since + DOUBLE_PRESS),
+            })
+            .min()
+    }
+}
+
+/// Put a gesture recognizer in front of `tx`. Presses and releases sent to
+/// the returned sender are passed on as they are, followed by a
+/// `ShortPress`, `LongPress` or `DoublePress` once the gesture is known.
+/// Everything else is passed straight on.
+pub fn connect(tx: messages::Sender, double_presses: DoublePresses) -> messages::Sender {
+    let (input_tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut recognizer = Recognizer::new(double_presses);
+
+        loop {
+            let received = match recognizer.next_deadline() {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    match rx.recv_timeout(timeout) {
+                        Ok(received) => Some(received),
+                        Err(mpsc::RecvTimeoutError::Timeout) => None,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                None => match rx.recv() {
+                    Ok(received) => Some(received),
+                    Err(_) => break,
+                },
+            };
+
+            for message in recognizer.process(received, Instant::now()) {
+                tx.send(message).unwrap();
+            }
+        }
+    });
+
+    input_tx
+}
+
+/// Only buttons that belong to controllers have gestures.
+fn has_gestures(id: ButtonId) -> bool {
+    matches!(id, ButtonId::Physical(_) | ButtonId::Controller(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUTTON: ButtonId = ButtonId::Physical(0);
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn names(output: Vec<Message>) -> Vec<&'static str> {
+        output
+            .into_iter()
+            .map(|message| match message {
+                Message::ButtonPress(_) => "press",
+                Message::ButtonRelease(_) => "release",
+                Message::ShortPress(_) => "short",
+                Message::LongPress(_) => "long",
+                Message::DoublePress(_) => "double",
+                _ => "other",
+            })
+            .collect()
+    }
+
+    /// Presses and releases at the given times, returning everything sent
+    /// along with the deadlines that passed in between.
+    fn run(double_bound: bool, events: &[(u64, bool)], end: u64) -> Vec<&'static str> {
+        let double_presses = DoublePresses::default();
+        if double_bound {
+            double_presses.set([BUTTON].into_iter().collect());
+        }
+        let mut recognizer = Recognizer::new(double_presses);
+        let start = Instant::now();
+        let mut output = Vec::new();
+
+        let mut events = events.iter().peekable();
+        loop {
+            let next_event = events.peek().map(|(at, _)| start + ms(*at));
+            let deadline = recognizer.next_deadline();
+            match (next_event, deadline) {
+                (Some(at), Some(deadline)) if deadline < at => {
+                    output.extend(recognizer.process(None, deadline));
+                }
+                (Some(at), _) => {
+                    let (_, down) = events.next().unwrap();
+                    let message = if *down {
+                        Message::ButtonPress(BUTTON)
+                    } else {
+                        Message::ButtonRelease(BUTTON)
+                    };
+                    output.extend(recognizer.process(Some(message), at));
+                }
+                (None, Some(deadline)) if deadline <= start + ms(end) => {
+                    output.extend(recognizer.process(None, deadline));
+                }
+                (None, _) => break,
+            }
+        }
+
+        names(output)
+    }
+
+    #[test]
+    fn short_press() {
+        let output = run(true, &[(0, true), (100, false)], 2000);
+        assert_eq!(output, ["press", "release", "short"]);
+
+        // Without a double press bound it doesn't wait.
+        let double_presses = DoublePresses::default();
+        let mut recognizer = Recognizer::new(double_presses);
+        let now = Instant::now();
+        recognizer.process(Some(Message::ButtonPress(BUTTON)), now);
+        let output = recognizer.process(Some(Message::ButtonRelease(BUTTON)), now + ms(100));
+        assert_eq!(names(output), ["release", "short"]);
+        assert!(recognizer.next_deadline().is_none());
+    }
+
+    #[test]
+    fn short_press_waits_for_the_double_press_window() {
+        let mut recognizer = Recognizer::new(DoublePresses::default());
+        recognizer
+            .double_presses
+            .set([BUTTON].into_iter().collect());
+        let now = Instant::now();
+        recognizer.process(Some(Message::ButtonPress(BUTTON)), now);
+        recognizer.process(Some(Message::ButtonRelease(BUTTON)), now + ms(100));
+        assert_eq!(
+            recognizer.next_deadline(),
+            Some(now + ms(100) + DOUBLE_PRESS)
+        );
+
+        let output = recognizer.process(None, now + ms(399));
+        assert!(output.is_empty());
+        let output = recognizer.process(None, now + ms(400));
+        assert_eq!(names(output), ["short"]);
+    }
+
+    #[test]
+    fn long_press() {
+        let output = run(true, &[(0, true), (1000, false)], 2000);
+        assert_eq!(output, ["press", "long", "release"]);
+    }
+
+    #[test]
+    fn long_press_is_sent_while_held() {
+        let mut recognizer = Recognizer::new(DoublePresses::default());
+        let now = Instant::now();
+        recognizer.process(Some(Message::ButtonPress(BUTTON)), now);
+        assert_eq!(recognizer.next_deadline(), Some(now + LONG_PRESS));
+        assert!(recognizer.process(None, now + ms(799)).is_empty());
+        assert_eq!(names(recognizer.process(None, now + ms(800))), ["long"]);
+        assert!(recognizer.next_deadline().is_none());
+    }
+
+    #[test]
+    fn release_after_the_long_threshold() {
+        // The release arrives before the deadline was handled.
+        let mut recognizer = Recognizer::new(DoublePresses::default());
+        let now = Instant::now();
+        recognizer.process(Some(Message::ButtonPress(BUTTON)), now);
+        let output = recognizer.process(Some(Message::ButtonRelease(BUTTON)), now + ms(900));
+        assert_eq!(names(output), ["long", "release"]);
+        assert!(recognizer.next_deadline().is_none());
+    }
+
+    #[test]
+    fn double_press() {
+        let output = run(
+            true,
+            &[(0, true), (100, false), (300, true), (400, false)],
+            2000,
+        );
+        assert_eq!(output, ["press", "release", "press", "double", "release"]);
+    }
+
+    #[test]
+    fn double_press_not_bound() {
+        let output = run(
+            false,
+            &[(0, true), (100, false), (300, true), (400, false)],
+            2000,
+        );
+        assert_eq!(
+            output,
+            ["press", "release", "short", "press", "release", "short"]
+        );
+    }
+
+    #[test]
+    fn presses_just_past_the_double_window() {
+        let output = run(
+            true,
+            &[(0, true), (100, false), (401, true), (500, false)],
+            2000,
+        );
+        assert_eq!(
+            output,
+            ["press", "release", "short", "press", "release", "short"]
+        );
+    }
+
+    #[test]
+    fn other_buttons_pass_straight_through() {
+        let mut recognizer = Recognizer::new(DoublePresses::default());
+        let now = Instant::now();
+        let output = recognizer.process(Some(Message::ButtonPress(ButtonId::PageUp)), now);
+        assert_eq!(names(output), ["press"]);
+        assert!(recognizer.next_deadline().is_none());
+    }
+}
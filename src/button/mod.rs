@@ -1,6 +1,6 @@
 use std::fmt::Debug;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 #[allow(dead_code)]
 pub enum ButtonId {
     Physical(usize),
@@ -10,8 +10,13 @@ pub enum ButtonId {
     NotAButton,
 }
 
+pub mod gestures;
+
 #[cfg(feature = "lca2021_badge")]
 pub mod touch;
 
 #[cfg(any(feature = "lca2021_badge", feature = "robotica"))]
 pub mod gpio;
+
+#[cfg(feature = "host")]
+pub mod stdin;
@@ -0,0 +1,81 @@
+use std::io;
+use std::io::BufRead;
+use std::thread;
+use std::time::Duration;
+
+use log::*;
+
+use crate::messages;
+use crate::messages::Message::ButtonPress;
+use crate::messages::Message::ButtonRelease;
+
+use super::gestures;
+use super::ButtonId;
+
+const HELP: &str = "Commands: <n> or p <n> press button n on this page, \
+                    c <n> press controller n, l <n> long press and \
+                    2 <n> double press button n, u page up, d page down";
+
+/// Hold a long press a bit longer than needed, so it isn't taken for a short
+/// one if this thread is woken a little early or the recognizer a little late.
+const LONG_PRESS_MARGIN: Duration = Duration::from_millis(100);
+
+enum Gesture {
+    Short,
+    Long,
+    Double,
+}
+
+fn parse_command(line: &str) -> Option<(Gesture, ButtonId)> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    let (gesture, id) = match words.as_slice() {
+        ["u"] => (Gesture::Short, Some(ButtonId::PageUp)),
+        ["d"] => (Gesture::Short, Some(ButtonId::PageDown)),
+        ["p", id] => (Gesture::Short, id.parse().ok().map(ButtonId::Physical)),
+        ["c", id] => (Gesture::Short, id.parse().ok().map(ButtonId::Controller)),
+        ["l", id] => (Gesture::Long, id.parse().ok().map(ButtonId::Physical)),
+        ["2", id] => (Gesture::Double, id.parse().ok().map(ButtonId::Physical)),
+        [id] => (Gesture::Short, id.parse().ok().map(ButtonId::Physical)),
+        _ => return None,
+    };
+
+    id.map(|id| (gesture, id))
+}
+
+pub fn configure_stdin(tx: messages::Sender) {
+    info!("{}", HELP);
+
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                // The rest of the line has been read, so carry on from the
+                // next one.
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                    warn!("Ignoring a line that is not UTF-8: {}", err);
+                    continue;
+                }
+                Err(err) => {
+                    error!("Stopped reading stdin: {}", err);
+                    break;
+                }
+            };
+            match parse_command(&line) {
+                Some((gesture, id)) => {
+                    tx.send(ButtonPress(id)).unwrap();
+                    match gesture {
+                        Gesture::Short => {}
+                        Gesture::Long => thread::sleep(gestures::LONG_PRESS + LONG_PRESS_MARGIN),
+                        Gesture::Double => {
+                            tx.send(ButtonRelease(id)).unwrap();
+                            tx.send(ButtonPress(id)).unwrap();
+                        }
+                    }
+                    tx.send(ButtonRelease(id)).unwrap();
+                }
+                None => warn!("Unknown command {:?}. {}", line, HELP),
+            }
+        }
+    });
+}
@@ -0,0 +1,340 @@
+use serde::Deserialize;
+
+use crate::button_controllers::*;
+
+#[derive(Clone, Deserialize)]
+pub struct ClimateConfig {
+    pub name: String,
+    pub topic_substr: String,
+    /// Presses cycle through these, in order.
+    #[serde(default = "default_modes")]
+    pub modes: Vec<String>,
+    /// How far page up and page down move the setpoint.
+    #[serde(default = "default_step")]
+    pub step: f64,
+    #[serde(default = "default_min")]
+    pub min: f64,
+    #[serde(default = "default_max")]
+    pub max: f64,
+    #[serde(default)]
+    pub unit: String,
+    #[serde(default = "default_decimals")]
+    pub decimals: usize,
+    #[serde(default)]
+    pub qos: QoS,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+fn default_modes() -> Vec<String> {
+    ["off", "heat", "cool", "auto"]
+        .iter()
+        .map(|mode| mode.to_string())
+        .collect()
+}
+
+fn default_step() -> f64 {
+    0.5
+}
+
+fn default_min() -> f64 {
+    5.0
+}
+
+fn default_max() -> f64 {
+    35.0
+}
+
+fn default_decimals() -> usize {
+    1
+}
+
+impl Config for ClimateConfig {
+    fn create_controller(&self) -> Box<dyn Controller> {
+        Box::new(ClimateController::new(self))
+    }
+}
+
+pub struct ClimateController {
+    config: ClimateConfig,
+    temperature: Option<f64>,
+    setpoint: Option<f64>,
+    /// The last setpoint we asked for, until the device reports its own.
+    requested: Option<f64>,
+    mode: Option<String>,
+}
+
+impl ClimateController {
+    pub fn new(config: &ClimateConfig) -> Self {
+        Self {
+            config: config.clone(),
+            temperature: None,
+            setpoint: None,
+            requested: None,
+            mode: None,
+        }
+    }
+
+    fn get_setpoint(&self) -> Option<f64> {
+        self.requested.or(self.setpoint)
+    }
+
+    /// The mode after the current one, starting again at the first.
+    fn get_next_mode(&self) -> Option<&String> {
+        let modes = &self.config.modes;
+        let next = match &self.mode {
+            Some(mode) => match modes.iter().position(|m| m == mode) {
+                Some(index) => index + 1,
+                None => 0,
+            },
+            None => 0,
+        };
+        modes.get(next).or_else(|| modes.first())
+    }
+
+    fn format(&self, value: Option<f64>) -> String {
+        match value {
+            Some(value) => format!("{:.*}{}", self.config.decimals, value, self.config.unit),
+            None => "--".to_string(),
+        }
+    }
+
+    fn get_command(&self, message: serde_json::Value) -> Command {
+        Command {
+            topic: format!("command/{}", self.config.topic_substr),
+            message,
+            qos: self.config.qos,
+            retain: self.config.retain,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+fn topic(parts: &[&str]) -> String {
+    parts.join("/")
+}
+
+fn parse_number(data: &str) -> Option<f64> {
+    data.trim().parse().ok()
+}
+
+impl Controller for ClimateController {
+    fn get_subscriptions(&self) -> Vec<Subscription> {
+        let mut result: Vec<Subscription> = Vec::new();
+        let config = &self.config;
+
+        let p = ["state", &config.topic_substr, "temperature"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::Temperature as u32,
+            qos: config.qos,
+        };
+        result.push(s);
+
+        let p = ["state", &config.topic_substr, "setpoint"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::Setpoint as u32,
+            qos: config.qos,
+        };
+        result.push(s);
+
+        let p = ["state", &config.topic_substr, "mode"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::Mode as u32,
+            qos: config.qos,
+        };
+        result.push(s);
+
+        result
+    }
+
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
+        match label.try_into() {
+            Ok(ButtonStateMsgType::Temperature) => match parse_number(&data) {
+                Some(temperature) => self.temperature = Some(temperature),
+                None => error!("Invalid temperature value {}", data),
+            },
+
+            Ok(ButtonStateMsgType::Setpoint) => match parse_number(&data) {
+                Some(setpoint) => {
+                    self.setpoint = Some(setpoint);
+                    self.requested = None;
+                }
+                None => error!("Invalid setpoint value {}", data),
+            },
+
+            Ok(ButtonStateMsgType::Mode) => self.mode = Some(data),
+
+            _ => error!("Invalid message label {}", label),
+        }
+
+        Ok(())
+    }
+
+    fn process_disconnected(&mut self) {
+        self.temperature = None;
+        self.setpoint = None;
+        self.requested = None;
+        self.mode = None;
+    }
+
+    fn get_display_state(&self) -> DisplayState {
+        match self.mode.as_deref() {
+            None => DisplayState::Unknown,
+            Some("HARD_OFF") => DisplayState::HardOff,
+            Some("off") => DisplayState::Off,
+            Some(mode) if self.config.modes.iter().any(|m| m == mode) => DisplayState::On,
+            Some(_) => DisplayState::OnOther,
+        }
+    }
+
+    fn get_extra(&self) -> Extra {
+        Extra::Setpoint(
+            self.format(self.temperature),
+            self.format(self.get_setpoint()),
+        )
+    }
+
+    fn get_press_commands(&self) -> Vec<Command> {
+        match self.get_next_mode() {
+            Some(mode) => vec![self.get_command(serde_json::json!({ "mode": mode }))],
+            None => vec![],
+        }
+    }
+
+    fn get_adjustable(&self) -> bool {
+        true
+    }
+
+    /// Move the setpoint by one step, on from any step we asked for that the
+    /// device hasn't reported yet.
+    fn adjust(&mut self, up: bool) -> Vec<Command> {
+        let setpoint = match self.get_setpoint() {
+            Some(setpoint) => setpoint,
+            None => return vec![],
+        };
+
+        let step = self.config.step;
+        let setpoint = if up { setpoint + step } else { setpoint - step };
+        let setpoint = ((setpoint / step).round() * step).clamp(self.config.min, self.config.max);
+        self.requested = Some(setpoint);
+
+        vec![self.get_command(serde_json::json!({ "setpoint": setpoint }))]
+    }
+
+    /// Not drawn, the temperatures are shown instead.
+    fn get_icon(&self) -> Icon {
+        Icon::Light
+    }
+
+    fn get_name(&self) -> String {
+        self.config.name.clone()
+    }
+
+    fn get_display_name(&self) -> String {
+        match &self.mode {
+            Some(mode) => format!("{}: {}", self.get_name(), mode),
+            None => self.get_name(),
+        }
+    }
+}
+
+enum ButtonStateMsgType {
+    Temperature,
+    Setpoint,
+    Mode,
+}
+
+impl TryFrom<u32> for ButtonStateMsgType {
+    type Error = ();
+
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ButtonStateMsgType::Temperature as u32 => Ok(ButtonStateMsgType::Temperature),
+            x if x == ButtonStateMsgType::Setpoint as u32 => Ok(ButtonStateMsgType::Setpoint),
+            x if x == ButtonStateMsgType::Mode as u32 => Ok(ButtonStateMsgType::Mode),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETPOINT: u32 = ButtonStateMsgType::Setpoint as u32;
+    const MODE: u32 = ButtonStateMsgType::Mode as u32;
+
+    fn climate() -> ClimateController {
+        let config =
+            serde_json::from_str(r#"{"name": "Heat pump", "topic_substr": "HeatPump"}"#).unwrap();
+        ClimateController::new(&config)
+    }
+
+    fn adjust(climate: &mut ClimateController, up: bool) -> Vec<String> {
+        climate
+            .adjust(up)
+            .iter()
+            .map(|command| command.get_message())
+            .collect()
+    }
+
+    fn next_mode(climate: &mut ClimateController, mode: &str) -> String {
+        climate.process_message(MODE, mode.to_string()).unwrap();
+        climate.get_press_commands()[0].get_message()
+    }
+
+    #[test]
+    fn adjust_steps_on_from_the_requested_setpoint() {
+        let mut climate = climate();
+        assert!(adjust(&mut climate, true).is_empty());
+
+        climate.process_message(SETPOINT, "20".to_string()).unwrap();
+        assert_eq!(adjust(&mut climate, true), [r#"{"setpoint":20.5}"#]);
+        assert_eq!(adjust(&mut climate, true), [r#"{"setpoint":21.0}"#]);
+        assert_eq!(
+            climate.get_extra(),
+            Extra::Setpoint("--".to_string(), "21.0".to_string())
+        );
+
+        // The device's own setpoint replaces what was asked for.
+        climate.process_message(SETPOINT, "19".to_string()).unwrap();
+        assert_eq!(adjust(&mut climate, false), [r#"{"setpoint":18.5}"#]);
+    }
+
+    #[test]
+    fn adjust_clamps_the_setpoint() {
+        let mut climate = climate();
+        climate
+            .process_message(SETPOINT, "34.8".to_string())
+            .unwrap();
+        assert_eq!(adjust(&mut climate, true), [r#"{"setpoint":35.0}"#]);
+        assert_eq!(adjust(&mut climate, true), [r#"{"setpoint":35.0}"#]);
+
+        climate
+            .process_message(SETPOINT, "5.2".to_string())
+            .unwrap();
+        assert_eq!(adjust(&mut climate, false), [r#"{"setpoint":5.0}"#]);
+        assert_eq!(adjust(&mut climate, false), [r#"{"setpoint":5.0}"#]);
+    }
+
+    #[test]
+    fn presses_cycle_through_the_modes() {
+        let mut climate = climate();
+        assert_eq!(
+            climate.get_press_commands()[0].get_message(),
+            r#"{"mode":"off"}"#
+        );
+        assert_eq!(next_mode(&mut climate, "off"), r#"{"mode":"heat"}"#);
+        assert_eq!(next_mode(&mut climate, "heat"), r#"{"mode":"cool"}"#);
+        assert_eq!(next_mode(&mut climate, "cool"), r#"{"mode":"auto"}"#);
+        assert_eq!(next_mode(&mut climate, "auto"), r#"{"mode":"off"}"#);
+
+        // A mode that isn't in the list starts again at the first.
+        assert_eq!(next_mode(&mut climate, "dry"), r#"{"mode":"off"}"#);
+        assert_eq!(climate.get_display_state(), DisplayState::OnOther);
+        assert_eq!(climate.get_display_name(), "Heat pump: dry");
+    }
+}
@@ -0,0 +1,292 @@
+use serde::{Deserialize, Serialize};
+
+use crate::button_controllers::*;
+
+#[derive(Clone, Deserialize)]
+pub struct CountdownConfig {
+    pub name: String,
+    pub icon: Icon,
+    /// Seconds added by each press.
+    pub increment: u64,
+    /// A press that would go past this many seconds cancels the countdown
+    /// instead, four increments by default.
+    #[serde(default)]
+    pub max: Option<u64>,
+    /// Sent when the countdown reaches zero.
+    pub command: CommandConfig,
+    /// Keep the remaining time, to the minute, over a reboot.
+    #[serde(default)]
+    pub persist: bool,
+    #[serde(default)]
+    pub qos: QoS,
+}
+
+impl Config for CountdownConfig {
+    fn create_controller(&self) -> Box<dyn Controller> {
+        Box::new(CountdownController::new(self))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    name: String,
+    /// Seconds, rounded up to the minute.
+    remaining: u64,
+}
+
+pub struct CountdownController {
+    config: CountdownConfig,
+    deadline: Option<Instant>,
+    /// As of the last press or tick.
+    remaining: Duration,
+}
+
+impl CountdownController {
+    pub fn new(config: &CountdownConfig) -> Self {
+        Self {
+            config: config.clone(),
+            deadline: None,
+            remaining: Duration::ZERO,
+        }
+    }
+
+    fn get_max(&self) -> Duration {
+        let max = self.config.max.unwrap_or(self.config.increment * 4);
+        Duration::from_secs(max)
+    }
+
+    fn start(&mut self, now: Instant, remaining: Duration) {
+        self.deadline = Some(now + remaining);
+        self.remaining = remaining;
+    }
+
+    fn stop(&mut self) {
+        self.deadline = None;
+        self.remaining = Duration::ZERO;
+    }
+}
+
+/// Whole seconds, rounded up so that the last second shows as 0:01.
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+fn format_remaining(remaining: Duration) -> String {
+    let secs = ceil_secs(remaining);
+    let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, mins, secs)
+    } else {
+        format!("{}:{:02}", mins, secs)
+    }
+}
+
+impl Controller for CountdownController {
+    fn get_subscriptions(&self) -> Vec<Subscription> {
+        vec![]
+    }
+
+    fn process_message(&mut self, label: Label, _data: String) -> anyhow::Result<()> {
+        error!("Invalid message label {}", label);
+        Ok(())
+    }
+
+    /// The countdown runs on the remote, so it carries on regardless.
+    fn process_disconnected(&mut self) {}
+
+    fn process_press(&mut self, now: Instant) {
+        let remaining = match self.deadline {
+            Some(deadline) => deadline.saturating_duration_since(now),
+            None => Duration::ZERO,
+        };
+
+        let remaining = remaining + Duration::from_secs(self.config.increment);
+        if remaining > self.get_max() {
+            info!("Cancelling countdown {}", self.config.name);
+            self.stop();
+        } else {
+            self.start(now, remaining);
+        }
+    }
+
+    fn process_tick(&mut self, now: Instant) -> Vec<Command> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return vec![],
+        };
+
+        self.remaining = deadline.saturating_duration_since(now);
+        if !self.remaining.is_zero() {
+            return vec![];
+        }
+
+        info!("Countdown {} done", self.config.name);
+        self.stop();
+
+        vec![self.config.command.to_command(self.config.qos)]
+    }
+
+    fn get_saved_state(&self) -> Option<String> {
+        if !self.config.persist {
+            return None;
+        }
+
+        let state = SavedState {
+            name: self.config.name.clone(),
+            remaining: (ceil_secs(self.remaining) + 59) / 60 * 60,
+        };
+        serde_json::to_string(&state).ok()
+    }
+
+    fn restore_state(&mut self, state: &str) {
+        if !self.config.persist {
+            return;
+        }
+
+        match serde_json::from_str::<SavedState>(state) {
+            Ok(state) if state.name == self.config.name && state.remaining > 0 => {
+                info!(
+                    "Resuming countdown {} with {}s left",
+                    state.name, state.remaining
+                );
+                self.start(Instant::now(), Duration::from_secs(state.remaining));
+            }
+            Ok(_) => {}
+            Err(err) => error!("Invalid saved countdown {}: {}", state, err),
+        }
+    }
+
+    fn get_display_state(&self) -> DisplayState {
+        match self.deadline {
+            Some(_) => DisplayState::On,
+            None => DisplayState::Off,
+        }
+    }
+
+    fn get_extra(&self) -> Extra {
+        match self.deadline {
+            Some(_) => Extra::Value(format_remaining(self.remaining)),
+            None => Extra::None,
+        }
+    }
+
+    /// Presses only change the countdown, see `process_press`.
+    fn get_press_commands(&self) -> Vec<Command> {
+        vec![]
+    }
+
+    fn get_icon(&self) -> Icon {
+        self.config.icon.clone()
+    }
+
+    fn get_name(&self) -> String {
+        self.config.name.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn countdown(extra: &str) -> CountdownController {
+        let config = serde_json::from_str(&format!(
+            r#"{{"name": "Tea", "icon": "light", "increment": 60,
+                "command": {{"topic": "kettle", "message": "off"}} {}}}"#,
+            extra
+        ))
+        .unwrap();
+        CountdownController::new(&config)
+    }
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    fn shown(countdown: &CountdownController) -> Extra {
+        countdown.get_extra()
+    }
+
+    fn value(text: &str) -> Extra {
+        Extra::Value(text.to_string())
+    }
+
+    #[test]
+    fn ticks_down_and_sends_the_command() {
+        let mut countdown = countdown("");
+        let now = Instant::now();
+        assert_eq!(countdown.get_display_state(), DisplayState::Off);
+
+        countdown.process_press(now);
+        assert_eq!(countdown.get_display_state(), DisplayState::On);
+        assert_eq!(shown(&countdown), value("1:00"));
+
+        assert!(countdown.process_tick(now + secs(30.0)).is_empty());
+        assert_eq!(shown(&countdown), value("0:30"));
+        assert!(countdown.process_tick(now + secs(59.5)).is_empty());
+        assert_eq!(shown(&countdown), value("0:01"));
+
+        let commands = countdown.process_tick(now + secs(60.0));
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].get_topic(), "kettle");
+        assert_eq!(commands[0].get_message(), "off");
+        assert_eq!(countdown.get_display_state(), DisplayState::Off);
+        assert!(countdown.process_tick(now + secs(61.0)).is_empty());
+    }
+
+    #[test]
+    fn presses_add_time_up_to_the_max() {
+        let mut countdown = countdown("");
+        let now = Instant::now();
+        for _ in 0..4 {
+            countdown.process_press(now);
+        }
+        assert_eq!(shown(&countdown), value("4:00"));
+
+        countdown.process_press(now);
+        assert_eq!(countdown.get_display_state(), DisplayState::Off);
+
+        let mut countdown = self::countdown(r#", "max": 90"#);
+        countdown.process_press(now);
+        countdown.process_press(now + secs(20.0));
+        assert_eq!(countdown.get_display_state(), DisplayState::Off);
+        countdown.process_press(now + secs(40.0));
+        countdown.process_tick(now + secs(70.0));
+        countdown.process_press(now + secs(70.0));
+        assert_eq!(shown(&countdown), value("1:30"));
+    }
+
+    #[test]
+    fn saved_state_is_rounded_up_to_the_minute() {
+        let mut countdown = countdown(r#", "persist": true"#);
+        let now = Instant::now();
+        countdown.process_press(now);
+        countdown.process_press(now);
+        countdown.process_tick(now + secs(30.5));
+
+        let saved = countdown.get_saved_state().unwrap();
+        assert_eq!(saved, r#"{"name":"Tea","remaining":120}"#);
+
+        let mut restored = self::countdown(r#", "persist": true"#);
+        restored.restore_state(&saved);
+        assert_eq!(restored.get_display_state(), DisplayState::On);
+        assert_eq!(shown(&restored), value("2:00"));
+    }
+
+    #[test]
+    fn restore_ignores_other_countdowns() {
+        let mut countdown = countdown(r#", "persist": true"#);
+        for saved in [
+            r#"{"name":"Eggs","remaining":120}"#,
+            r#"{"name":"Tea","remaining":0}"#,
+            "not json",
+        ] {
+            countdown.restore_state(saved);
+            assert_eq!(countdown.get_display_state(), DisplayState::Off);
+        }
+
+        let mut countdown = self::countdown("");
+        assert!(countdown.get_saved_state().is_none());
+        countdown.restore_state(r#"{"name":"Tea","remaining":120}"#);
+        assert_eq!(countdown.get_display_state(), DisplayState::Off);
+    }
+}
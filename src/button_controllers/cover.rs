@@ -0,0 +1,273 @@
+use serde::Deserialize;
+
+use crate::button_controllers::*;
+
+#[derive(Clone, Deserialize)]
+pub struct CoverConfig {
+    #[serde(flatten)]
+    pub c: CommonConfig,
+}
+
+impl Config for CoverConfig {
+    fn create_controller(&self) -> Box<dyn Controller> {
+        Box::new(CoverController::new(self))
+    }
+}
+
+pub struct CoverController {
+    config: CoverConfig,
+    state: Option<String>,
+    position: Option<u8>,
+    /// Which way the cover moved last, so a press after a stop goes the
+    /// other way.
+    last_motion: Motion,
+}
+
+impl CoverController {
+    pub fn new(config: &CoverConfig) -> Self {
+        Self {
+            config: config.clone(),
+            state: None,
+            position: None,
+            last_motion: Motion::Stopped,
+        }
+    }
+
+    fn get_motion(&self) -> Motion {
+        match self.state.as_deref() {
+            Some("opening") => Motion::Opening,
+            Some("closing") => Motion::Closing,
+            _ => Motion::Stopped,
+        }
+    }
+
+    fn get_position(&self) -> Option<u8> {
+        match (self.position, self.state.as_deref()) {
+            (Some(position), _) => Some(position),
+            (None, Some("open")) => Some(100),
+            (None, Some("closed")) => Some(0),
+            (None, _) => None,
+        }
+    }
+
+    /// Presses go open, stop, close, stop. Whatever the cover is doing, a
+    /// press while it moves stops it.
+    fn get_next_action(&self) -> &'static str {
+        match self.get_motion() {
+            Motion::Opening | Motion::Closing => return "stop",
+            Motion::Stopped => {}
+        }
+
+        match (self.state.as_deref(), &self.last_motion) {
+            (Some("open"), _) => "close",
+            (Some("closed"), _) => "open",
+            (_, Motion::Opening) => "close",
+            (_, _) => "open",
+        }
+    }
+}
+
+fn topic(parts: &[&str]) -> String {
+    parts.join("/")
+}
+
+impl Controller for CoverController {
+    fn get_subscriptions(&self) -> Vec<Subscription> {
+        let mut result: Vec<Subscription> = Vec::new();
+        let config = &self.config;
+
+        let p = ["state", &config.c.topic_substr, "state"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::State as u32,
+            qos: config.c.qos,
+        };
+        result.push(s);
+
+        let p = ["state", &config.c.topic_substr, "position"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::Position as u32,
+            qos: config.c.qos,
+        };
+        result.push(s);
+
+        result
+    }
+
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
+        match label.try_into() {
+            Ok(ButtonStateMsgType::State) => {
+                self.state = Some(data);
+                match self.get_motion() {
+                    Motion::Stopped => {}
+                    motion => self.last_motion = motion,
+                }
+            }
+
+            Ok(ButtonStateMsgType::Position) => match data.trim().parse::<u8>() {
+                Ok(position) if position <= 100 => self.position = Some(position),
+                _ => error!("Invalid position value {}", data),
+            },
+
+            _ => error!("Invalid message label {}", label),
+        }
+
+        Ok(())
+    }
+
+    fn process_disconnected(&mut self) {
+        self.state = None;
+        self.position = None;
+    }
+
+    fn get_display_state(&self) -> DisplayState {
+        let state = match self.state.as_deref() {
+            None => DisplayState::Unknown,
+            Some("HARD_OFF") => DisplayState::HardOff,
+            Some("opening") | Some("closing") => DisplayState::OnOther,
+            Some("open") => DisplayState::On,
+            Some("closed") => DisplayState::Off,
+            Some("stopped") => match self.get_position() {
+                Some(0) => DisplayState::Off,
+                Some(100) => DisplayState::On,
+                _ => DisplayState::OnOther,
+            },
+            Some(_) => DisplayState::Error,
+        };
+
+        let action = &self.config.c.action;
+        get_display_state_for_action(state, action)
+    }
+
+    fn get_extra(&self) -> Extra {
+        match self.get_position() {
+            Some(position) => Extra::Position(position, self.get_motion()),
+            None => Extra::None,
+        }
+    }
+
+    fn get_action_commands(&self, action: &Action) -> Vec<Command> {
+        let action = match action {
+            Action::TurnOn => "open",
+            Action::TurnOff => "close",
+            Action::Toggle => self.get_next_action(),
+        };
+
+        let message = serde_json::json!({ "action": action });
+
+        let topic = format!("command/{}", self.config.c.topic_substr);
+        let command = Command {
+            topic,
+            message,
+            qos: self.config.c.qos,
+            retain: self.config.c.retain,
+            delay: Duration::ZERO,
+        };
+
+        vec![command]
+    }
+
+    fn get_press_commands(&self) -> Vec<Command> {
+        self.get_action_commands(&self.config.c.action)
+    }
+
+    fn get_icon(&self) -> Icon {
+        self.config.c.icon.clone()
+    }
+
+    fn get_name(&self) -> String {
+        self.config.c.name.clone()
+    }
+}
+
+enum ButtonStateMsgType {
+    State,
+    Position,
+}
+
+impl TryFrom<u32> for ButtonStateMsgType {
+    type Error = ();
+
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ButtonStateMsgType::State as u32 => Ok(ButtonStateMsgType::State),
+            x if x == ButtonStateMsgType::Position as u32 => Ok(ButtonStateMsgType::Position),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATE: u32 = ButtonStateMsgType::State as u32;
+    const POSITION: u32 = ButtonStateMsgType::Position as u32;
+
+    fn cover() -> CoverController {
+        let config = serde_json::from_str(
+            r#"{"name": "Blind", "topic_substr": "Blind", "action": "toggle", "icon": "light"}"#,
+        )
+        .unwrap();
+        CoverController::new(&config)
+    }
+
+    fn next_action(cover: &CoverController) -> String {
+        cover.get_press_commands()[0].get_message()
+    }
+
+    fn set_state(cover: &mut CoverController, state: &str) {
+        cover.process_message(STATE, state.to_string()).unwrap();
+    }
+
+    #[test]
+    fn presses_go_open_stop_close_stop() {
+        let mut cover = cover();
+        assert_eq!(next_action(&cover), r#"{"action":"open"}"#);
+
+        set_state(&mut cover, "closed");
+        assert_eq!(cover.get_display_state(), DisplayState::Off);
+        assert_eq!(next_action(&cover), r#"{"action":"open"}"#);
+
+        set_state(&mut cover, "opening");
+        assert_eq!(cover.get_display_state(), DisplayState::OnOther);
+        assert_eq!(next_action(&cover), r#"{"action":"stop"}"#);
+
+        set_state(&mut cover, "stopped");
+        assert_eq!(next_action(&cover), r#"{"action":"close"}"#);
+
+        set_state(&mut cover, "closing");
+        assert_eq!(next_action(&cover), r#"{"action":"stop"}"#);
+
+        set_state(&mut cover, "stopped");
+        assert_eq!(next_action(&cover), r#"{"action":"open"}"#);
+
+        set_state(&mut cover, "open");
+        assert_eq!(cover.get_display_state(), DisplayState::On);
+        assert_eq!(next_action(&cover), r#"{"action":"close"}"#);
+    }
+
+    #[test]
+    fn stopped_state_follows_the_position() {
+        let mut cover = cover();
+        set_state(&mut cover, "opening");
+        cover.process_message(POSITION, "40".to_string()).unwrap();
+        assert_eq!(cover.get_extra(), Extra::Position(40, Motion::Opening));
+
+        set_state(&mut cover, "stopped");
+        assert_eq!(cover.get_display_state(), DisplayState::OnOther);
+        assert_eq!(cover.get_extra(), Extra::Position(40, Motion::Stopped));
+
+        for (position, state) in [("0", DisplayState::Off), ("100", DisplayState::On)] {
+            cover
+                .process_message(POSITION, position.to_string())
+                .unwrap();
+            assert_eq!(cover.get_display_state(), state);
+        }
+
+        // Out of range positions are ignored.
+        cover.process_message(POSITION, "101".to_string()).unwrap();
+        assert_eq!(cover.get_extra(), Extra::Position(100, Motion::Stopped));
+    }
+}
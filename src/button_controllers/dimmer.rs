@@ -0,0 +1,282 @@
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+use crate::button_controllers::*;
+
+#[derive(Clone, Deserialize)]
+pub struct DimmerConfig {
+    #[serde(flatten)]
+    pub c: CommonConfig,
+    #[serde(default = "default_levels", deserialize_with = "deserialize_levels")]
+    pub levels: Vec<u8>,
+}
+
+fn default_levels() -> Vec<u8> {
+    vec![25, 50, 75, 100]
+}
+
+fn deserialize_levels<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut levels = Vec::<u8>::deserialize(deserializer)?;
+
+    if let Some(level) = levels.iter().find(|level| **level > 100) {
+        return Err(D::Error::custom(format!(
+            "invalid level {}, expected 0 to 100",
+            level
+        )));
+    }
+
+    levels.retain(|level| *level > 0);
+    levels.sort_unstable();
+    levels.dedup();
+
+    if levels.is_empty() {
+        return Err(D::Error::custom("expected at least one level above 0"));
+    }
+
+    Ok(levels)
+}
+
+impl Config for DimmerConfig {
+    fn create_controller(&self) -> Box<dyn Controller> {
+        Box::new(DimmerController::new(self))
+    }
+}
+
+pub struct DimmerController {
+    config: DimmerConfig,
+    power: Option<String>,
+    brightness: Option<u8>,
+}
+
+impl DimmerController {
+    pub fn new(config: &DimmerConfig) -> Self {
+        Self {
+            config: config.clone(),
+            power: None,
+            brightness: None,
+        }
+    }
+
+    /// The current brightness, 0 if the light is off.
+    fn get_level(&self) -> Option<u8> {
+        match self.power.as_deref() {
+            Some("ON") => self.brightness,
+            Some("OFF") | Some("HARD_OFF") => Some(0),
+            _ => None,
+        }
+    }
+
+    /// Each press steps up to the next level. After the highest level
+    /// toggle turns the light off, and turn_on starts again at the lowest.
+    fn get_next_level(&self, action: &Action) -> u8 {
+        let levels = &self.config.levels;
+        let current = self.get_level().unwrap_or(0);
+        let next = levels.iter().copied().find(|level| *level > current);
+
+        match (action, next) {
+            (Action::TurnOff, _) => 0,
+            (_, Some(level)) => level,
+            (Action::TurnOn, None) => levels[0],
+            (Action::Toggle, None) => 0,
+        }
+    }
+}
+
+fn topic(parts: &[&str]) -> String {
+    parts.join("/")
+}
+
+impl Controller for DimmerController {
+    fn get_subscriptions(&self) -> Vec<Subscription> {
+        let mut result: Vec<Subscription> = Vec::new();
+        let config = &self.config;
+
+        let p = ["state", &config.c.topic_substr, "power"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::Power as u32,
+            qos: config.c.qos,
+        };
+        result.push(s);
+
+        let p = ["state", &config.c.topic_substr, "brightness"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::Brightness as u32,
+            qos: config.c.qos,
+        };
+        result.push(s);
+
+        result
+    }
+
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
+        match label.try_into() {
+            Ok(ButtonStateMsgType::Power) => self.power = Some(data),
+
+            Ok(ButtonStateMsgType::Brightness) => match data.trim().parse::<u8>() {
+                Ok(brightness) if brightness <= 100 => self.brightness = Some(brightness),
+                _ => error!("Invalid brightness value {}", data),
+            },
+
+            _ => error!("Invalid message label {}", label),
+        }
+
+        Ok(())
+    }
+
+    fn process_disconnected(&mut self) {
+        self.power = None;
+        self.brightness = None;
+    }
+
+    fn get_display_state(&self) -> DisplayState {
+        let state = match self.power.as_deref() {
+            None => DisplayState::Unknown,
+            Some("HARD_OFF") => DisplayState::HardOff,
+            Some("ON") => DisplayState::On,
+            Some("OFF") => DisplayState::Off,
+            _ => DisplayState::Error,
+        };
+
+        let action = &self.config.c.action;
+        get_display_state_for_action(state, action)
+    }
+
+    fn get_extra(&self) -> Extra {
+        match self.get_level() {
+            Some(level) => Extra::Level(level),
+            None => Extra::None,
+        }
+    }
+
+    fn get_action_commands(&self, action: &Action) -> Vec<Command> {
+        let message = match self.get_next_level(action) {
+            0 => serde_json::json!({
+                "action": "turn_off",
+            }),
+            level => serde_json::json!({
+                "action": "turn_on",
+                "brightness": level,
+            }),
+        };
+
+        let topic = format!("command/{}", self.config.c.topic_substr);
+        let command = Command {
+            topic,
+            message,
+            qos: self.config.c.qos,
+            retain: self.config.c.retain,
+            delay: Duration::ZERO,
+        };
+
+        vec![command]
+    }
+
+    fn get_press_commands(&self) -> Vec<Command> {
+        self.get_action_commands(&self.config.c.action)
+    }
+
+    fn get_icon(&self) -> Icon {
+        self.config.c.icon.clone()
+    }
+
+    fn get_name(&self) -> String {
+        self.config.c.name.clone()
+    }
+}
+
+enum ButtonStateMsgType {
+    Power,
+    Brightness,
+}
+
+impl TryFrom<u32> for ButtonStateMsgType {
+    type Error = ();
+
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ButtonStateMsgType::Power as u32 => Ok(ButtonStateMsgType::Power),
+            x if x == ButtonStateMsgType::Brightness as u32 => Ok(ButtonStateMsgType::Brightness),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POWER: u32 = ButtonStateMsgType::Power as u32;
+    const BRIGHTNESS: u32 = ButtonStateMsgType::Brightness as u32;
+
+    fn config(extra: &str) -> serde_json::Result<DimmerConfig> {
+        serde_json::from_str(&format!(
+            r#"{{"name": "Lamp", "topic_substr": "Lamp", "action": "toggle", "icon": "light" {}}}"#,
+            extra
+        ))
+    }
+
+    fn dimmer() -> DimmerController {
+        DimmerController::new(&config("").unwrap())
+    }
+
+    /// The message a press sends at this level, `None` for off.
+    fn next(dimmer: &mut DimmerController, action: &Action, level: Option<u8>) -> String {
+        match level {
+            Some(level) => {
+                dimmer.process_message(POWER, "ON".to_string()).unwrap();
+                dimmer
+                    .process_message(BRIGHTNESS, level.to_string())
+                    .unwrap();
+            }
+            None => dimmer.process_message(POWER, "OFF".to_string()).unwrap(),
+        }
+        dimmer.get_action_commands(action)[0].get_message()
+    }
+
+    fn turn_on(level: u8) -> String {
+        format!(r#"{{"action":"turn_on","brightness":{}}}"#, level)
+    }
+
+    const TURN_OFF: &str = r#"{"action":"turn_off"}"#;
+
+    #[test]
+    fn toggle_steps_up_then_turns_off() {
+        let mut dimmer = dimmer();
+        let toggle = &Action::Toggle;
+        assert_eq!(next(&mut dimmer, toggle, None), turn_on(25));
+        assert_eq!(dimmer.get_extra(), Extra::Level(0));
+        assert_eq!(next(&mut dimmer, toggle, Some(25)), turn_on(50));
+        assert_eq!(dimmer.get_extra(), Extra::Level(25));
+        assert_eq!(next(&mut dimmer, toggle, Some(60)), turn_on(75));
+        assert_eq!(next(&mut dimmer, toggle, Some(75)), turn_on(100));
+        assert_eq!(next(&mut dimmer, toggle, Some(100)), TURN_OFF);
+    }
+
+    #[test]
+    fn turn_on_wraps_to_the_lowest_level() {
+        let mut dimmer = dimmer();
+        let turn_on_action = &Action::TurnOn;
+        assert_eq!(next(&mut dimmer, turn_on_action, Some(75)), turn_on(100));
+        assert_eq!(next(&mut dimmer, turn_on_action, Some(100)), turn_on(25));
+
+        let turn_off_action = &Action::TurnOff;
+        assert_eq!(next(&mut dimmer, turn_off_action, Some(25)), TURN_OFF);
+        assert_eq!(next(&mut dimmer, turn_off_action, None), TURN_OFF);
+    }
+
+    #[test]
+    fn levels_are_sorted_without_zero() {
+        let levels = config(r#", "levels": [100, 0, 50, 50]"#).unwrap().levels;
+        assert_eq!(levels, [50, 100]);
+
+        for levels in ["[101]", "[0]", "[]"] {
+            let extra = format!(r#", "levels": {}"#, levels);
+            assert!(config(&extra).is_err(), "levels {}", levels);
+        }
+    }
+}
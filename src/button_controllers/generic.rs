@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::button_controllers::*;
+
+#[derive(Clone, Deserialize)]
+pub struct GenericConfig {
+    pub name: String,
+    pub action: Action,
+    pub icon: Icon,
+    pub state_topic: String,
+    /// Read the state from this path into a JSON message, otherwise the
+    /// whole message is the state.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Maps each state value to what the tile shows.
+    pub states: HashMap<String, DisplayState>,
+    /// Shown for values that aren't in `states`.
+    #[serde(default = "default_other_state")]
+    pub other_state: DisplayState,
+    pub command_topic: String,
+    pub commands: Commands,
+    #[serde(default)]
+    pub qos: QoS,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+/// Messages to send for each action. Strings are sent as is, anything else
+/// as JSON. Without a `toggle` message toggling sends `on` or `off`.
+#[derive(Clone, Deserialize)]
+pub struct Commands {
+    pub on: Value,
+    pub off: Value,
+    #[serde(default)]
+    pub toggle: Option<Value>,
+}
+
+fn default_other_state() -> DisplayState {
+    DisplayState::Error
+}
+
+impl Config for GenericConfig {
+    fn create_controller(&self) -> Box<dyn Controller> {
+        Box::new(GenericController::new(self))
+    }
+}
+
+pub struct GenericController {
+    config: GenericConfig,
+    state: Option<DisplayState>,
+}
+
+impl GenericController {
+    pub fn new(config: &GenericConfig) -> Self {
+        Self {
+            config: config.clone(),
+            state: None,
+        }
+    }
+
+    /// The mapped state, before the tile's action is applied.
+    fn get_raw_state(&self) -> DisplayState {
+        match &self.state {
+            Some(state) => state.clone(),
+            None => DisplayState::Unknown,
+        }
+    }
+
+    fn parse_state(&self, data: &str) -> Option<DisplayState> {
+        let value = match &self.config.path {
+            Some(path) => {
+                let json: Value = serde_json::from_str(data).ok()?;
+                match get_json_path(&json, path)? {
+                    Value::String(string) => string.clone(),
+                    value => value.to_string(),
+                }
+            }
+            None => data.to_string(),
+        };
+
+        let state = match self.config.states.get(&value) {
+            Some(state) => state.clone(),
+            None => {
+                warn!("No state for value {}", value);
+                self.config.other_state.clone()
+            }
+        };
+        Some(state)
+    }
+}
+
+impl Controller for GenericController {
+    fn get_subscriptions(&self) -> Vec<Subscription> {
+        let s = Subscription {
+            topic: self.config.state_topic.clone(),
+            label: ButtonStateMsgType::State as u32,
+            qos: self.config.qos,
+        };
+
+        vec![s]
+    }
+
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
+        match label.try_into() {
+            Ok(ButtonStateMsgType::State) => match self.parse_state(&data) {
+                Some(state) => self.state = Some(state),
+                None => anyhow::bail!("no value at {:?} in {}", self.config.path, data),
+            },
+
+            _ => error!("Invalid message label {}", label),
+        }
+
+        Ok(())
+    }
+
+    fn process_disconnected(&mut self) {
+        self.state = None;
+    }
+
+    fn get_display_state(&self) -> DisplayState {
+        let action = &self.config.action;
+        get_display_state_for_action(self.get_raw_state(), action)
+    }
+
+    fn get_action_commands(&self, action: &Action) -> Vec<Command> {
+        let commands = &self.config.commands;
+        let message = match (action, &commands.toggle) {
+            (Action::TurnOn, _) => &commands.on,
+            (Action::TurnOff, _) => &commands.off,
+            (Action::Toggle, Some(toggle)) => toggle,
+            (Action::Toggle, None) => match self.get_raw_state() {
+                DisplayState::On => &commands.off,
+                _ => &commands.on,
+            },
+        };
+
+        let command = Command {
+            topic: self.config.command_topic.clone(),
+            message: message.clone(),
+            qos: self.config.qos,
+            retain: self.config.retain,
+            delay: Duration::ZERO,
+        };
+
+        vec![command]
+    }
+
+    fn get_press_commands(&self) -> Vec<Command> {
+        self.get_action_commands(&self.config.action)
+    }
+
+    fn get_icon(&self) -> Icon {
+        self.config.icon.clone()
+    }
+
+    fn get_name(&self) -> String {
+        self.config.name.clone()
+    }
+}
+
+enum ButtonStateMsgType {
+    State,
+}
+
+impl TryFrom<u32> for ButtonStateMsgType {
+    type Error = ();
+
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ButtonStateMsgType::State as u32 => Ok(ButtonStateMsgType::State),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATE: u32 = ButtonStateMsgType::State as u32;
+
+    fn generic(extra: &str) -> GenericController {
+        let config = serde_json::from_str(&format!(
+            r#"{{"name": "Heater", "action": "toggle", "icon": "fan",
+                "state_topic": "heater/state", "command_topic": "heater/set",
+                "states": {{"on": "on", "off": "off", "1": "on", "0": "off", "true": "on"}},
+                "commands": {{"on": "on", "off": "off"}} {}}}"#,
+            extra
+        ))
+        .unwrap();
+        GenericController::new(&config)
+    }
+
+    fn state_after(generic: &mut GenericController, data: &str) -> DisplayState {
+        generic.process_message(STATE, data.to_string()).unwrap();
+        generic.get_display_state()
+    }
+
+    #[test]
+    fn whole_message_is_the_state() {
+        let mut generic = generic("");
+        assert_eq!(generic.get_display_state(), DisplayState::Unknown);
+        assert_eq!(state_after(&mut generic, "on"), DisplayState::On);
+        assert_eq!(state_after(&mut generic, "off"), DisplayState::Off);
+        assert_eq!(state_after(&mut generic, "ON"), DisplayState::Error);
+    }
+
+    #[test]
+    fn other_state() {
+        let mut generic = generic(r#", "other_state": "on_other""#);
+        assert_eq!(state_after(&mut generic, "heating"), DisplayState::OnOther);
+    }
+
+    #[test]
+    fn state_from_a_json_path() {
+        let mut generic = generic(r#", "path": "status.power""#);
+        let state = state_after(&mut generic, r#"{"status": {"power": "on"}}"#);
+        assert_eq!(state, DisplayState::On);
+
+        // Values that aren't strings are looked up as JSON.
+        let state = state_after(&mut generic, r#"{"status": {"power": 0}}"#);
+        assert_eq!(state, DisplayState::Off);
+        let state = state_after(&mut generic, r#"{"status": {"power": true}}"#);
+        assert_eq!(state, DisplayState::On);
+        let state = state_after(&mut generic, r#"{"status": {"power": "standby"}}"#);
+        assert_eq!(state, DisplayState::Error);
+    }
+
+    #[test]
+    fn missing_path_is_an_error_and_keeps_the_state() {
+        let mut generic = generic(r#", "path": "status.power""#);
+        state_after(&mut generic, r#"{"status": {"power": "on"}}"#);
+
+        for data in ["on", r#"{"status": {}}"#, r#"{"power": "off"}"#] {
+            assert!(generic.process_message(STATE, data.to_string()).is_err());
+            assert_eq!(generic.get_display_state(), DisplayState::On);
+        }
+    }
+
+    #[test]
+    fn toggle_sends_the_opposite_without_a_toggle_command() {
+        let mut generic = generic("");
+        let message = |generic: &GenericController| generic.get_press_commands()[0].get_message();
+        assert_eq!(message(&generic), "on");
+        state_after(&mut generic, "on");
+        assert_eq!(message(&generic), "off");
+    }
+}
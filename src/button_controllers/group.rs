@@ -0,0 +1,302 @@
+use serde::Deserialize;
+
+use crate::button_controllers::lights::LightConfig;
+use crate::button_controllers::switch::SwitchConfig;
+use crate::button_controllers::*;
+
+#[derive(Clone, Deserialize)]
+pub struct GroupConfig {
+    pub name: String,
+    pub icon: Icon,
+    pub members: Vec<MemberConfig>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MemberConfig {
+    Switch(SwitchConfig),
+    Light(LightConfig),
+}
+
+impl Config for GroupConfig {
+    fn create_controller(&self) -> Box<dyn Controller> {
+        Box::new(GroupController::new(self))
+    }
+}
+
+struct Member {
+    controller: Box<dyn Controller>,
+    invalid: InvalidLabels,
+}
+
+impl Member {
+    /// The group decides what is on and off, so members always report their
+    /// state as turn_on buttons.
+    fn new(config: &MemberConfig) -> Self {
+        let controller = match config {
+            MemberConfig::Switch(config) => {
+                let mut config = config.clone();
+                config.c.action = Action::TurnOn;
+                config.create_controller()
+            }
+            MemberConfig::Light(config) => {
+                let mut config = config.clone();
+                config.c.action = Action::TurnOn;
+                config.create_controller()
+            }
+        };
+
+        Member {
+            controller,
+            invalid: InvalidLabels::default(),
+        }
+    }
+
+    fn get_display_state(&self) -> DisplayState {
+        self.invalid
+            .get_display_state(self.controller.get_display_state())
+    }
+}
+
+// Member labels are packed into the group's label, the member's own label
+// in the low byte.
+const MEMBER_SHIFT: u32 = 8;
+
+pub struct GroupController {
+    config: GroupConfig,
+    members: Vec<Member>,
+}
+
+impl GroupController {
+    pub fn new(config: &GroupConfig) -> Self {
+        Self {
+            config: config.clone(),
+            members: config.members.iter().map(Member::new).collect(),
+        }
+    }
+}
+
+impl Controller for GroupController {
+    fn get_subscriptions(&self) -> Vec<Subscription> {
+        let mut result: Vec<Subscription> = Vec::new();
+
+        for (index, member) in self.members.iter().enumerate() {
+            for s in member.controller.get_subscriptions() {
+                result.push(Subscription {
+                    topic: s.topic,
+                    label: (index as u32) << MEMBER_SHIFT | s.label,
+                    qos: s.qos,
+                });
+            }
+        }
+
+        result
+    }
+
+    /// A member's bad message only makes that member an error.
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
+        let index = (label >> MEMBER_SHIFT) as usize;
+        let label = label & ((1 << MEMBER_SHIFT) - 1);
+
+        match self.members.get_mut(index) {
+            Some(member) => {
+                let result = member.controller.process_message(label, data);
+                member.invalid.record(label, result);
+            }
+            None => error!("Invalid group member {}", index),
+        }
+
+        Ok(())
+    }
+
+    fn process_disconnected(&mut self) {
+        for member in &mut self.members {
+            member.controller.process_disconnected();
+            member.invalid.clear();
+        }
+    }
+
+    /// On if every member that can be switched is on, Off if none are, and
+    /// OnOther in between. Members that are hard off, unknown or in error
+    /// don't count unless there are no others.
+    fn get_display_state(&self) -> DisplayState {
+        let states: Vec<DisplayState> = self
+            .members
+            .iter()
+            .map(|member| member.get_display_state())
+            .collect();
+
+        let known: Vec<&DisplayState> = states
+            .iter()
+            .filter(|state| {
+                matches!(
+                    state,
+                    DisplayState::On | DisplayState::OnOther | DisplayState::Off
+                )
+            })
+            .collect();
+
+        if known.is_empty() {
+            return if states.iter().all(|state| *state == DisplayState::HardOff) {
+                DisplayState::HardOff
+            } else if states.contains(&DisplayState::Error) {
+                DisplayState::Error
+            } else {
+                DisplayState::Unknown
+            };
+        }
+
+        let num_on = known
+            .iter()
+            .filter(|state| ***state != DisplayState::Off)
+            .count();
+
+        if num_on == known.len() {
+            DisplayState::On
+        } else if num_on == 0 {
+            DisplayState::Off
+        } else {
+            DisplayState::OnOther
+        }
+    }
+
+    /// Toggling turns everything off if it is all on, otherwise turns
+    /// everything on.
+    fn get_action_commands(&self, action: &Action) -> Vec<Command> {
+        let action = match (action, self.get_display_state()) {
+            (Action::Toggle, DisplayState::On) => Action::TurnOff,
+            (Action::Toggle, _) => Action::TurnOn,
+            (action, _) => action.clone(),
+        };
+
+        self.members
+            .iter()
+            .filter(|member| member.get_display_state() != DisplayState::HardOff)
+            .flat_map(|member| member.controller.get_action_commands(&action))
+            .collect()
+    }
+
+    fn get_press_commands(&self) -> Vec<Command> {
+        self.get_action_commands(&Action::Toggle)
+    }
+
+    fn get_icon(&self) -> Icon {
+        self.config.icon.clone()
+    }
+
+    fn get_name(&self) -> String {
+        self.config.name.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group() -> GroupController {
+        let member = |name: &str| {
+            format!(
+                r#"{{"type": "switch", "name": "{}", "topic_substr": "{}", "action": "toggle",
+                    "icon": "fan"}}"#,
+                name, name
+            )
+        };
+        let config = serde_json::from_str(&format!(
+            r#"{{"name": "All", "icon": "light", "members": [{}, {}, {}]}}"#,
+            member("A"),
+            member("B"),
+            member("C")
+        ))
+        .unwrap();
+        GroupController::new(&config)
+    }
+
+    /// Set each member's power, `None` leaves it unknown.
+    fn state_of(powers: [Option<&str>; 3]) -> DisplayState {
+        let mut group = group();
+        for (index, power) in powers.iter().enumerate() {
+            if let Some(power) = power {
+                let label = (index as u32) << MEMBER_SHIFT;
+                group.process_message(label, power.to_string()).unwrap();
+            }
+        }
+        group.get_display_state()
+    }
+
+    #[test]
+    fn member_labels_are_packed() {
+        let group = group();
+        let subscriptions: Vec<(String, u32)> = group
+            .get_subscriptions()
+            .into_iter()
+            .map(|s| (s.topic, s.label))
+            .collect();
+        assert_eq!(
+            subscriptions,
+            [
+                ("state/A/power".to_string(), 0),
+                ("state/B/power".to_string(), 0x100),
+                ("state/C/power".to_string(), 0x200),
+            ]
+        );
+    }
+
+    #[test]
+    fn messages_go_to_the_packed_member() {
+        let mut group = group();
+        group.process_message(0x100, "ON".to_string()).unwrap();
+        assert_eq!(group.members[0].get_display_state(), DisplayState::Unknown);
+        assert_eq!(group.members[1].get_display_state(), DisplayState::On);
+
+        // Unknown members are ignored.
+        group.process_message(0x300, "ON".to_string()).unwrap();
+        assert_eq!(group.get_display_state(), DisplayState::On);
+    }
+
+    #[test]
+    fn state_counts_members_that_can_be_switched() {
+        assert_eq!(
+            state_of([Some("ON"), Some("ON"), Some("ON")]),
+            DisplayState::On
+        );
+        assert_eq!(
+            state_of([Some("OFF"), Some("OFF"), None]),
+            DisplayState::Off
+        );
+        assert_eq!(
+            state_of([Some("ON"), Some("OFF"), None]),
+            DisplayState::OnOther
+        );
+        assert_eq!(
+            state_of([Some("HARD_OFF"), Some("ON"), Some("bad")]),
+            DisplayState::On
+        );
+    }
+
+    #[test]
+    fn state_without_members_that_can_be_switched() {
+        assert_eq!(state_of([None, None, None]), DisplayState::Unknown);
+        let hard_off = Some("HARD_OFF");
+        assert_eq!(
+            state_of([hard_off, hard_off, hard_off]),
+            DisplayState::HardOff
+        );
+        assert_eq!(state_of([hard_off, None, hard_off]), DisplayState::Unknown);
+        assert_eq!(state_of([hard_off, None, Some("bad")]), DisplayState::Error);
+    }
+
+    #[test]
+    fn toggle_skips_hard_off_members() {
+        let mut group = group();
+        group.process_message(0, "HARD_OFF".to_string()).unwrap();
+        group.process_message(0x100, "ON".to_string()).unwrap();
+        group.process_message(0x200, "OFF".to_string()).unwrap();
+
+        let commands = group.get_press_commands();
+        let topics: Vec<&str> = commands.iter().map(|c| c.get_topic()).collect();
+        assert_eq!(topics, ["command/B", "command/C"]);
+        assert!(commands
+            .iter()
+            .all(|c| c.get_message() == r#"{"action":"turn_on"}"#));
+    }
+}
@@ -1,7 +1,10 @@
+use serde::Deserialize;
+
 use crate::button_controllers::*;
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize)]
 pub struct LightConfig {
+    #[serde(flatten)]
     pub c: CommonConfig,
     pub scene: String,
     pub priority: Priority,
@@ -29,6 +32,12 @@ impl LightController {
             priorities: None,
         }
     }
+
+    /// Whether the light is showing this tile's scene, whatever the tile's
+    /// action.
+    fn get_raw_state(&self) -> DisplayState {
+        get_display_state_toggle(self)
+    }
 }
 
 fn topic(parts: &[&str]) -> String {
@@ -44,6 +53,7 @@ impl Controller for LightController {
         let s = Subscription {
             topic: topic(&p),
             label: ButtonStateMsgType::Power as u32,
+            qos: config.c.qos,
         };
         result.push(s);
 
@@ -51,6 +61,7 @@ impl Controller for LightController {
         let s = Subscription {
             topic: topic(&p),
             label: ButtonStateMsgType::Scenes as u32,
+            qos: config.c.qos,
         };
         result.push(s);
 
@@ -58,13 +69,14 @@ impl Controller for LightController {
         let s = Subscription {
             topic: topic(&p),
             label: ButtonStateMsgType::Priorities as u32,
+            qos: config.c.qos,
         };
         result.push(s);
 
         result
     }
 
-    fn process_message(&mut self, label: Label, data: String) {
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
         match label.try_into() {
             Ok(ButtonStateMsgType::Power) => self.power = Some(data),
 
@@ -80,6 +92,8 @@ impl Controller for LightController {
 
             _ => error!("Invalid message label {}", label),
         }
+
+        Ok(())
     }
 
     fn process_disconnected(&mut self) {
@@ -98,29 +112,38 @@ impl Controller for LightController {
         }
     }
 
-    fn get_press_commands(&self) -> Vec<Command> {
+    fn get_action_commands(&self, action: &Action) -> Vec<Command> {
         let mut message = serde_json::json!({
             "scene": self.config.scene,
             "priority": self.config.priority,
         });
 
-        match self.config.c.action {
+        match action {
             Action::TurnOn => {}
             Action::TurnOff => message["action"] = serde_json::json!("turn_off"),
             Action::Toggle => {
-                let display_state = self.get_display_state();
-                if let DisplayState::On = display_state {
+                if let DisplayState::On = self.get_raw_state() {
                     message["action"] = serde_json::json!("turn_off");
                 };
             }
         };
 
         let topic = format!("command/{}", self.config.c.topic_substr);
-        let command = Command { topic, message };
+        let command = Command {
+            topic,
+            message,
+            qos: self.config.c.qos,
+            retain: self.config.c.retain,
+            delay: Duration::ZERO,
+        };
 
         vec![command]
     }
 
+    fn get_press_commands(&self) -> Vec<Command> {
+        self.get_action_commands(&self.config.c.action)
+    }
+
     fn get_icon(&self) -> Icon {
         self.config.c.icon.clone()
     }
@@ -225,3 +248,63 @@ impl TryFrom<u32> for ButtonStateMsgType {
 }
 
 type Priority = i32;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn light() -> LightController {
+        light_with_action("toggle")
+    }
+
+    fn light_with_action(action: &str) -> LightController {
+        let config = serde_json::from_str(&format!(
+            r#"{{"name": "Lamp", "topic_substr": "Lamp", "action": "{}", "icon": "light",
+                "scene": "default", "priority": 100}}"#,
+            action
+        ))
+        .unwrap();
+        LightController::new(&config)
+    }
+
+    #[test]
+    fn toggle_on_a_turn_off_tile() {
+        let mut light = light_with_action("turn_off");
+        light
+            .process_message(ButtonStateMsgType::Power as u32, "ON".to_string())
+            .unwrap();
+        light
+            .process_message(
+                ButtonStateMsgType::Scenes as u32,
+                r#"["default"]"#.to_string(),
+            )
+            .unwrap();
+        light
+            .process_message(ButtonStateMsgType::Priorities as u32, "[100]".to_string())
+            .unwrap();
+        assert_eq!(light.get_display_state(), DisplayState::Off);
+
+        let commands = light.get_action_commands(&Action::Toggle);
+        assert_eq!(
+            commands[0].get_message(),
+            r#"{"action":"turn_off","priority":100,"scene":"default"}"#
+        );
+
+        light
+            .process_message(ButtonStateMsgType::Power as u32, "OFF".to_string())
+            .unwrap();
+        light
+            .process_message(ButtonStateMsgType::Scenes as u32, "[]".to_string())
+            .unwrap();
+        light
+            .process_message(ButtonStateMsgType::Priorities as u32, "[]".to_string())
+            .unwrap();
+        assert_eq!(light.get_display_state(), DisplayState::On);
+
+        let commands = light.get_action_commands(&Action::Toggle);
+        assert_eq!(
+            commands[0].get_message(),
+            r#"{"priority":100,"scene":"default"}"#
+        );
+    }
+}
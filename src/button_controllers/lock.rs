@@ -0,0 +1,143 @@
+use serde::Deserialize;
+
+use crate::button_controllers::*;
+
+#[derive(Clone, Deserialize)]
+pub struct LockConfig {
+    pub name: String,
+    pub topic_substr: String,
+    pub icon: Icon,
+    /// Seconds to press again to confirm an unlock.
+    #[serde(default = "default_confirm_timeout")]
+    pub confirm_timeout: u64,
+    #[serde(default)]
+    pub qos: QoS,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+fn default_confirm_timeout() -> u64 {
+    5
+}
+
+impl Config for LockConfig {
+    fn create_controller(&self) -> Box<dyn Controller> {
+        Box::new(LockController::new(self))
+    }
+}
+
+pub struct LockController {
+    config: LockConfig,
+    state: Option<String>,
+}
+
+impl LockController {
+    pub fn new(config: &LockConfig) -> Self {
+        Self {
+            config: config.clone(),
+            state: None,
+        }
+    }
+
+    /// Unlock if it is locked, otherwise lock. Nothing until we know.
+    fn get_next_action(&self) -> Option<&'static str> {
+        match self.state.as_deref() {
+            None | Some("HARD_OFF") => None,
+            Some("locked") => Some("unlock"),
+            Some(_) => Some("lock"),
+        }
+    }
+}
+
+fn topic(parts: &[&str]) -> String {
+    parts.join("/")
+}
+
+impl Controller for LockController {
+    fn get_subscriptions(&self) -> Vec<Subscription> {
+        let p = ["state", &self.config.topic_substr, "state"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::State as u32,
+            qos: self.config.qos,
+        };
+
+        vec![s]
+    }
+
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
+        match label.try_into() {
+            Ok(ButtonStateMsgType::State) => self.state = Some(data),
+
+            _ => error!("Invalid message label {}", label),
+        }
+
+        Ok(())
+    }
+
+    fn process_disconnected(&mut self) {
+        self.state = None;
+    }
+
+    fn get_display_state(&self) -> DisplayState {
+        match self.state.as_deref() {
+            None => DisplayState::Unknown,
+            Some("HARD_OFF") => DisplayState::HardOff,
+            Some("locked") => DisplayState::On,
+            Some("unlocked") => DisplayState::Off,
+            Some("locking") | Some("unlocking") => DisplayState::OnOther,
+            Some(_) => DisplayState::Error,
+        }
+    }
+
+    fn get_press_commands(&self) -> Vec<Command> {
+        let action = match self.get_next_action() {
+            Some(action) => action,
+            None => return vec![],
+        };
+
+        let message = serde_json::json!({ "action": action });
+
+        let topic = format!("command/{}", self.config.topic_substr);
+        let command = Command {
+            topic,
+            message,
+            qos: self.config.qos,
+            retain: self.config.retain,
+            delay: Duration::ZERO,
+        };
+
+        vec![command]
+    }
+
+    /// Only unlocking needs confirming, locking is always safe.
+    fn get_press_confirm(&self) -> Option<Duration> {
+        match self.get_next_action() {
+            Some("unlock") => Some(Duration::from_secs(self.config.confirm_timeout)),
+            _ => None,
+        }
+    }
+
+    fn get_icon(&self) -> Icon {
+        self.config.icon.clone()
+    }
+
+    fn get_name(&self) -> String {
+        self.config.name.clone()
+    }
+}
+
+enum ButtonStateMsgType {
+    State,
+}
+
+impl TryFrom<u32> for ButtonStateMsgType {
+    type Error = ();
+
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ButtonStateMsgType::State as u32 => Ok(ButtonStateMsgType::State),
+            _ => Err(()),
+        }
+    }
+}
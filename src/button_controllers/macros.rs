@@ -0,0 +1,211 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::button_controllers::*;
+
+#[derive(Clone, Deserialize)]
+pub struct MacroConfig {
+    pub name: String,
+    pub icon: Icon,
+    pub steps: Vec<Step>,
+    /// The display state comes from this topic, if set.
+    #[serde(default)]
+    pub state_topic: Option<String>,
+    #[serde(default = "default_state_on")]
+    pub state_on: String,
+    #[serde(default)]
+    pub qos: QoS,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Step {
+    pub topic: String,
+    pub message: Value,
+    /// Seconds to wait after the previous step.
+    #[serde(default)]
+    pub delay: u64,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+fn default_state_on() -> String {
+    "ON".to_string()
+}
+
+impl Config for MacroConfig {
+    fn create_controller(&self) -> Box<dyn Controller> {
+        Box::new(MacroController::new(self))
+    }
+}
+
+pub struct MacroController {
+    config: MacroConfig,
+    state: Option<String>,
+}
+
+impl MacroController {
+    pub fn new(config: &MacroConfig) -> Self {
+        Self {
+            config: config.clone(),
+            state: None,
+        }
+    }
+}
+
+impl Controller for MacroController {
+    fn get_subscriptions(&self) -> Vec<Subscription> {
+        match &self.config.state_topic {
+            Some(topic) => vec![Subscription {
+                topic: topic.clone(),
+                label: ButtonStateMsgType::State as u32,
+                qos: self.config.qos,
+            }],
+            None => vec![],
+        }
+    }
+
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
+        match label.try_into() {
+            Ok(ButtonStateMsgType::State) => self.state = Some(data),
+
+            _ => error!("Invalid message label {}", label),
+        }
+
+        Ok(())
+    }
+
+    fn process_disconnected(&mut self) {
+        self.state = None;
+    }
+
+    fn get_display_state(&self) -> DisplayState {
+        if self.config.state_topic.is_none() {
+            return DisplayState::Off;
+        }
+
+        match self.state.as_deref() {
+            None => DisplayState::Unknown,
+            Some("HARD_OFF") => DisplayState::HardOff,
+            Some(state) if state == self.config.state_on => DisplayState::On,
+            Some(_) => DisplayState::Off,
+        }
+    }
+
+    fn get_press_commands(&self) -> Vec<Command> {
+        let mut delay = Duration::ZERO;
+
+        self.config
+            .steps
+            .iter()
+            .map(|step| {
+                delay += Duration::from_secs(step.delay);
+                Command {
+                    topic: step.topic.clone(),
+                    message: step.message.clone(),
+                    qos: self.config.qos,
+                    retain: step.retain,
+                    delay,
+                }
+            })
+            .collect()
+    }
+
+    /// Without a state topic there is nothing to wait for.
+    fn get_press_pending(&self) -> bool {
+        self.config.state_topic.is_some()
+    }
+
+    fn get_icon(&self) -> Icon {
+        self.config.icon.clone()
+    }
+
+    fn get_name(&self) -> String {
+        self.config.name.clone()
+    }
+}
+
+enum ButtonStateMsgType {
+    State,
+}
+
+impl TryFrom<u32> for ButtonStateMsgType {
+    type Error = ();
+
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ButtonStateMsgType::State as u32 => Ok(ButtonStateMsgType::State),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn macro_controller(extra: &str) -> MacroController {
+        let config = serde_json::from_str(&format!(
+            r#"{{"name": "Bedtime", "icon": "wake_up", "steps": [
+                {{"topic": "lights/off", "message": "OFF"}},
+                {{"topic": "blinds/close", "message": {{"action": "close"}}, "delay": 5}},
+                {{"topic": "fan/on", "message": "ON", "retain": true}},
+                {{"topic": "fan/off", "message": "OFF", "delay": 60}}]
+                {}}}"#,
+            extra
+        ))
+        .unwrap();
+        MacroController::new(&config)
+    }
+
+    #[test]
+    fn step_delays_add_up() {
+        let commands = macro_controller("").get_press_commands();
+        let steps: Vec<(&str, String, u64, bool)> = commands
+            .iter()
+            .map(|command| {
+                (
+                    command.get_topic(),
+                    command.get_message(),
+                    command.get_delay().as_secs(),
+                    command.get_retain(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            steps,
+            [
+                ("lights/off", "OFF".to_string(), 0, false),
+                (
+                    "blinds/close",
+                    r#"{"action":"close"}"#.to_string(),
+                    5,
+                    false
+                ),
+                ("fan/on", "ON".to_string(), 5, true),
+                ("fan/off", "OFF".to_string(), 65, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn state_comes_from_the_state_topic() {
+        let controller = macro_controller("");
+        assert!(controller.get_subscriptions().is_empty());
+        assert_eq!(controller.get_display_state(), DisplayState::Off);
+        assert!(!controller.get_press_pending());
+
+        let mut controller = macro_controller(r#", "state_topic": "bedtime", "state_on": "yes""#);
+        assert_eq!(controller.get_subscriptions()[0].topic, "bedtime");
+        assert_eq!(controller.get_display_state(), DisplayState::Unknown);
+        assert!(controller.get_press_pending());
+
+        let label = ButtonStateMsgType::State as u32;
+        controller
+            .process_message(label, "yes".to_string())
+            .unwrap();
+        assert_eq!(controller.get_display_state(), DisplayState::On);
+        controller.process_message(label, "ON".to_string()).unwrap();
+        assert_eq!(controller.get_display_state(), DisplayState::Off);
+    }
+}
@@ -1,17 +1,37 @@
+pub mod climate;
+pub mod countdown;
+pub mod cover;
+pub mod dimmer;
+pub mod generic;
+pub mod group;
 pub mod lights;
+pub mod lock;
+pub mod macros;
 pub mod music;
+pub mod presence;
+pub mod scenes;
+pub mod sensor;
 pub mod switch;
 
+use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::convert::TryInto;
+use std::time::{Duration, Instant};
 
 use log::*;
+use serde::Deserialize;
+
+use crate::mqtt::QoS;
 
 type Label = u32;
 
 pub struct Command {
     topic: String,
     message: serde_json::Value,
+    qos: QoS,
+    retain: bool,
+    /// How long after the press to send it.
+    delay: Duration,
 }
 
 impl Command {
@@ -20,11 +40,28 @@ impl Command {
     }
 
     pub fn get_message(&self) -> String {
-        self.message.to_string()
+        match &self.message {
+            // Plain strings are sent as is rather than as JSON.
+            serde_json::Value::String(message) => message.clone(),
+            message => message.to_string(),
+        }
+    }
+
+    pub fn get_qos(&self) -> QoS {
+        self.qos
+    }
+
+    pub fn get_retain(&self) -> bool {
+        self.retain
+    }
+
+    pub fn get_delay(&self) -> Duration {
+        self.delay
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
 #[allow(dead_code)]
 pub enum Action {
     TurnOn,
@@ -32,13 +69,92 @@ pub enum Action {
     Toggle,
 }
 
+/// A command given in the config, for controllers that send whatever they
+/// are told to.
+#[derive(Clone, Deserialize)]
+pub struct CommandConfig {
+    pub topic: String,
+    pub message: serde_json::Value,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+impl CommandConfig {
+    pub fn to_command(&self, qos: QoS) -> Command {
+        Command {
+            topic: self.topic.clone(),
+            message: self.message.clone(),
+            qos,
+            retain: self.retain,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Press {
+    Short,
+    Long,
+    Double,
+}
+
+/// What a long or double press does instead of the usual press.
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Binding {
+    Action(Action),
+    Commands(Vec<CommandConfig>),
+}
+
+/// Long and double press bindings, which any controller can have.
+#[derive(Clone, Default, Deserialize)]
+pub struct PressConfig {
+    #[serde(default)]
+    pub long_press: Option<Binding>,
+    #[serde(default)]
+    pub double_press: Option<Binding>,
+    #[serde(default)]
+    pub qos: QoS,
+}
+
+impl PressConfig {
+    /// Without bindings a press acts straight away, rather than waiting to
+    /// see what kind of press it is.
+    pub fn has_bindings(&self) -> bool {
+        self.long_press.is_some() || self.double_press.is_some()
+    }
+
+    /// None if the press does the usual thing.
+    pub fn get_binding(&self, press: Press) -> Option<&Binding> {
+        match press {
+            Press::Short => None,
+            Press::Long => self.long_press.as_ref(),
+            Press::Double => self.double_press.as_ref(),
+        }
+    }
+}
+
+impl Binding {
+    pub fn get_commands(&self, controller: &dyn Controller, qos: QoS) -> Vec<Command> {
+        match self {
+            Binding::Action(action) => controller.get_action_commands(action),
+            Binding::Commands(commands) => commands
+                .iter()
+                .map(|command| command.to_command(qos))
+                .collect(),
+        }
+    }
+}
+
 pub struct Subscription {
     pub topic: String,
     pub label: Label,
+    pub qos: QoS,
 }
 
 #[allow(dead_code)]
-#[derive(std::cmp::Eq, std::cmp::PartialEq, Clone, Debug)]
+#[derive(std::cmp::Eq, std::cmp::PartialEq, Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DisplayState {
     HardOff,
     Error,
@@ -46,35 +162,174 @@ pub enum DisplayState {
     On,
     Off,
     OnOther,
+    Pending,
+}
+
+/// Something shown alongside the state, if the controller has it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Extra {
+    None,
+    /// A percentage, e.g. brightness.
+    Level(u8),
+    /// Text that scrolls if it doesn't fit, e.g. the current track.
+    Text(String),
+    /// A reading that is shown instead of the icon.
+    Value(String),
+    /// How far a cover is open as a percentage, and which way it is moving.
+    Position(u8, Motion),
+    /// The current and target temperatures, shown instead of the icon.
+    Setpoint(String, String),
+    /// A short string that is the main thing on the tile, e.g. where someone
+    /// is, shown beside the icon.
+    Status(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Motion {
+    Stopped,
+    Opening,
+    Closing,
 }
 
 pub trait Config {
     fn create_controller(&self) -> Box<dyn Controller>;
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Icon {
     Light,
     Fan,
     WakeUp,
+    #[serde(rename = "tv")]
     TV,
+    Blind,
+    Home,
+    Away,
 }
-#[derive(Clone)]
+
+#[derive(Clone, Deserialize)]
 pub struct CommonConfig {
     pub name: String,
     pub topic_substr: String,
     pub action: Action,
     pub icon: Icon,
+    #[serde(default)]
+    pub qos: QoS,
+    #[serde(default)]
+    pub retain: bool,
 }
 
 pub trait Controller {
     fn get_subscriptions(&self) -> Vec<Subscription>;
     fn process_disconnected(&mut self);
-    fn process_message(&mut self, label: Label, data: String);
+    /// Handle a message on one of the subscriptions. An error means the
+    /// message could not be used, and the tile shows an error until the next
+    /// good message with that label.
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()>;
     fn get_display_state(&self) -> DisplayState;
+    fn get_extra(&self) -> Extra {
+        Extra::None
+    }
     fn get_press_commands(&self) -> Vec<Command>;
+    /// Commands for an action bound to a long or double press. Controllers
+    /// without on and off actions just do their usual press.
+    fn get_action_commands(&self, _action: &Action) -> Vec<Command> {
+        self.get_press_commands()
+    }
+    /// Called on each press before `get_press_commands`, for controllers
+    /// that keep their own state rather than following MQTT.
+    fn process_press(&mut self, _now: Instant) {}
+    /// Called every tick, returns any commands that have come due.
+    fn process_tick(&mut self, _now: Instant) -> Vec<Command> {
+        vec![]
+    }
+    /// State to keep over a reboot, saved whenever it changes.
+    fn get_saved_state(&self) -> Option<String> {
+        None
+    }
+    fn restore_state(&mut self, _state: &str) {}
+    /// Whether the tile shows `Pending` after a press until the state changes.
+    fn get_press_pending(&self) -> bool {
+        true
+    }
+    /// If set, a press only asks for confirmation, and a second press within
+    /// this long sends the commands.
+    fn get_press_confirm(&self) -> Option<Duration> {
+        None
+    }
+    /// Whether the first press focuses the tile, so that page up and page
+    /// down adjust it instead of changing page.
+    fn get_adjustable(&self) -> bool {
+        false
+    }
+    /// Nudge the controller up or down while it is focused.
+    fn adjust(&mut self, _up: bool) -> Vec<Command> {
+        vec![]
+    }
     fn get_icon(&self) -> Icon;
     fn get_name(&self) -> String;
+    /// The name shown on the tile, if it should change with the state.
+    fn get_display_name(&self) -> String {
+        self.get_name()
+    }
+}
+
+/// Labels whose last message could not be used, a controller with any of
+/// them shows as an error.
+#[derive(Default)]
+pub struct InvalidLabels(HashSet<Label>);
+
+impl InvalidLabels {
+    /// Decode a message and pass it on to the controller, remembering
+    /// whether it could be used.
+    pub fn process_message(
+        &mut self,
+        controller: &mut dyn Controller,
+        label: Label,
+        data: Vec<u8>,
+    ) {
+        let result = String::from_utf8(data)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| controller.process_message(label, data));
+        self.record(label, result);
+    }
+
+    /// Remember whether a message that was already decoded could be used.
+    pub fn record(&mut self, label: Label, result: anyhow::Result<()>) {
+        match result {
+            Ok(()) => {
+                self.0.remove(&label);
+            }
+            Err(err) => {
+                error!("Invalid message with label {}: {}", label, err);
+                self.0.insert(label);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn get_display_state(&self, state: DisplayState) -> DisplayState {
+        if self.0.is_empty() {
+            state
+        } else {
+            DisplayState::Error
+        }
+    }
+}
+
+/// Look up a dotted path such as `state` or `sensors.0.temperature` in a
+/// JSON value. An empty path is the value itself.
+fn get_json_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.')
+        .filter(|key| !key.is_empty())
+        .try_fold(value, |value, key| match value {
+            serde_json::Value::Array(array) => array.get(key.parse::<usize>().ok()?),
+            value => value.get(key),
+        })
 }
 
 fn get_display_state_for_action(state: DisplayState, action: &Action) -> DisplayState {
@@ -87,6 +342,7 @@ fn get_display_state_for_action(state: DisplayState, action: &Action) -> Display
             DisplayState::On => DisplayState::Off,
             DisplayState::Off => DisplayState::On,
             DisplayState::OnOther => DisplayState::Off,
+            DisplayState::Pending => DisplayState::Pending,
         },
         Action::Toggle => state,
     }
@@ -1,9 +1,38 @@
+use serde::Deserialize;
+
 use crate::button_controllers::*;
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize)]
 pub struct MusicConfig {
+    #[serde(flatten)]
     pub c: CommonConfig,
-    pub play_list: String,
+    #[serde(default)]
+    pub play_list: Option<String>,
+    /// Tiles with a volume action change the volume instead of playing.
+    #[serde(default)]
+    pub volume: Option<VolumeAction>,
+    #[serde(default = "default_volume_step")]
+    pub volume_step: u8,
+    /// Where the new volume goes in the command. By default this is the
+    /// music volume of a Robotica audio command, the same command that
+    /// `play_list` sends, which keeps the volume of messages separate.
+    #[serde(default = "default_volume_field")]
+    pub volume_field: String,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeAction {
+    Up,
+    Down,
+}
+
+fn default_volume_step() -> u8 {
+    10
+}
+
+fn default_volume_field() -> String {
+    "volume.music".to_string()
 }
 
 impl Config for MusicConfig {
@@ -15,6 +44,8 @@ impl Config for MusicConfig {
 pub struct MusicController {
     config: MusicConfig,
     play_list: Option<String>,
+    volume: Option<u8>,
+    track: Option<String>,
 }
 
 impl MusicController {
@@ -22,8 +53,48 @@ impl MusicController {
         Self {
             config: config.clone(),
             play_list: None,
+            volume: None,
+            track: None,
         }
     }
+
+    fn get_volume_display_state(&self) -> DisplayState {
+        match self.volume {
+            None => DisplayState::Unknown,
+            Some(0) => DisplayState::Off,
+            Some(_) => DisplayState::On,
+        }
+    }
+
+    fn get_volume_commands(&self, action: &VolumeAction) -> Vec<Command> {
+        let volume = match self.volume {
+            Some(volume) => volume,
+            None => return vec![],
+        };
+
+        let step = self.config.volume_step;
+        let volume = match action {
+            VolumeAction::Up => volume.saturating_add(step).min(100),
+            VolumeAction::Down => volume.saturating_sub(step),
+        };
+
+        // Nest the volume under each part of the path, innermost first.
+        let message = self.config.volume_field.rsplit('.').fold(
+            serde_json::json!(volume),
+            |value, key| serde_json::json!({ key: value }),
+        );
+
+        let topic = format!("command/{}", self.config.c.topic_substr);
+        let command = Command {
+            topic,
+            message,
+            qos: self.config.c.qos,
+            retain: self.config.c.retain,
+            delay: Duration::ZERO,
+        };
+
+        vec![command]
+    }
 }
 
 fn topic(parts: &[&str]) -> String {
@@ -39,31 +110,63 @@ impl Controller for MusicController {
         let s = Subscription {
             topic: topic(&p),
             label: ButtonStateMsgType::PlayList as u32,
+            qos: config.c.qos,
+        };
+        result.push(s);
+
+        let p = ["state", &config.c.topic_substr, "volume"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::Volume as u32,
+            qos: config.c.qos,
+        };
+        result.push(s);
+
+        let p = ["state", &config.c.topic_substr, "track"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::Track as u32,
+            qos: config.c.qos,
         };
         result.push(s);
 
         result
     }
 
-    fn process_message(&mut self, label: Label, data: String) {
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
         match label.try_into() {
             Ok(ButtonStateMsgType::PlayList) => self.play_list = Some(data),
 
+            Ok(ButtonStateMsgType::Volume) => match data.trim().parse::<u8>() {
+                Ok(volume) if volume <= 100 => self.volume = Some(volume),
+                _ => error!("Invalid volume value {}", data),
+            },
+
+            Ok(ButtonStateMsgType::Track) => self.track = Some(data),
+
             _ => error!("Invalid message label {}", label),
         }
+
+        Ok(())
     }
 
     fn process_disconnected(&mut self) {
         self.play_list = None;
+        self.volume = None;
+        self.track = None;
     }
 
     fn get_display_state(&self) -> DisplayState {
+        if self.config.volume.is_some() {
+            return self.get_volume_display_state();
+        }
+
         let play_list = self.play_list.as_deref();
         let state = match play_list {
             None => DisplayState::Unknown,
             Some("ERROR") => DisplayState::Error,
             Some("STOP") => DisplayState::Off,
-            Some(pl) if pl == self.config.play_list => DisplayState::On,
+            Some(pl) if Some(pl) == self.config.play_list.as_deref() => DisplayState::On,
             _ => DisplayState::OnOther,
         };
 
@@ -71,7 +174,26 @@ impl Controller for MusicController {
         get_display_state_for_action(state, action)
     }
 
+    fn get_extra(&self) -> Extra {
+        if self.config.volume.is_some() {
+            return match self.volume {
+                Some(volume) => Extra::Level(volume),
+                None => Extra::None,
+            };
+        }
+
+        match (self.play_list.as_deref(), self.track.as_deref()) {
+            (Some("STOP") | Some("ERROR") | None, _) => Extra::None,
+            (_, Some(track)) if !track.is_empty() => Extra::Text(track.to_string()),
+            _ => Extra::None,
+        }
+    }
+
     fn get_press_commands(&self) -> Vec<Command> {
+        if let Some(action) = &self.config.volume {
+            return self.get_volume_commands(action);
+        }
+
         let play = match self.config.c.action {
             Action::TurnOn => true,
             Action::TurnOff => false,
@@ -92,7 +214,13 @@ impl Controller for MusicController {
         };
 
         let topic = format!("command/{}", self.config.c.topic_substr);
-        let command = Command { topic, message };
+        let command = Command {
+            topic,
+            message,
+            qos: self.config.c.qos,
+            retain: self.config.c.retain,
+            delay: Duration::ZERO,
+        };
 
         vec![command]
     }
@@ -108,6 +236,8 @@ impl Controller for MusicController {
 
 enum ButtonStateMsgType {
     PlayList,
+    Volume,
+    Track,
 }
 
 impl TryFrom<u32> for ButtonStateMsgType {
@@ -116,7 +246,42 @@ impl TryFrom<u32> for ButtonStateMsgType {
     fn try_from(v: u32) -> Result<Self, Self::Error> {
         match v {
             x if x == ButtonStateMsgType::PlayList as u32 => Ok(ButtonStateMsgType::PlayList),
+            x if x == ButtonStateMsgType::Volume as u32 => Ok(ButtonStateMsgType::Volume),
+            x if x == ButtonStateMsgType::Track as u32 => Ok(ButtonStateMsgType::Track),
             _ => Err(()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume_up(extra: &str) -> MusicController {
+        let config = serde_json::from_str(&format!(
+            r#"{{"name": "Volume", "topic_substr": "Kitchen", "action": "toggle", "icon": "tv",
+                "volume": "up"{}}}"#,
+            extra
+        ))
+        .unwrap();
+        let mut music = MusicController::new(&config);
+        music
+            .process_message(ButtonStateMsgType::Volume as u32, "50".to_string())
+            .unwrap();
+        music
+    }
+
+    #[test]
+    fn volume_command() {
+        let commands = volume_up("").get_press_commands();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].get_topic(), "command/Kitchen");
+        assert_eq!(commands[0].get_message(), r#"{"volume":{"music":60}}"#);
+    }
+
+    #[test]
+    fn volume_field() {
+        let commands = volume_up(r#", "volume_field": "volume""#).get_press_commands();
+        assert_eq!(commands[0].get_message(), r#"{"volume":60}"#);
+    }
+}
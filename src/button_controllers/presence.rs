@@ -0,0 +1,222 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::button_controllers::*;
+
+#[derive(Clone, Deserialize)]
+pub struct PresenceConfig {
+    /// The person's name.
+    pub name: String,
+    pub topic: String,
+    /// Read the state from this path into a JSON message, otherwise the
+    /// whole message is the state.
+    #[serde(default)]
+    pub field: Option<String>,
+    #[serde(default = "default_home")]
+    pub home: String,
+    /// Any other state is the name of a zone.
+    #[serde(default = "default_away")]
+    pub away: Vec<String>,
+    #[serde(default)]
+    pub qos: QoS,
+}
+
+fn default_home() -> String {
+    "home".to_string()
+}
+
+fn default_away() -> Vec<String> {
+    vec!["away".to_string(), "not_home".to_string()]
+}
+
+impl Config for PresenceConfig {
+    fn create_controller(&self) -> Box<dyn Controller> {
+        Box::new(PresenceController::new(self))
+    }
+}
+
+enum Presence {
+    Home,
+    Away,
+    Zone(String),
+}
+
+pub struct PresenceController {
+    config: PresenceConfig,
+    presence: Option<Presence>,
+}
+
+impl PresenceController {
+    pub fn new(config: &PresenceConfig) -> Self {
+        Self {
+            config: config.clone(),
+            presence: None,
+        }
+    }
+
+    fn parse_state(&self, data: &str) -> Option<String> {
+        let state = match &self.config.field {
+            Some(field) => {
+                let json: Value = serde_json::from_str(data).ok()?;
+                get_json_path(&json, field)?.as_str()?.trim().to_string()
+            }
+            None => data.trim().to_string(),
+        };
+
+        Some(state).filter(|state| !state.is_empty())
+    }
+}
+
+impl Controller for PresenceController {
+    fn get_subscriptions(&self) -> Vec<Subscription> {
+        let s = Subscription {
+            topic: self.config.topic.clone(),
+            label: ButtonStateMsgType::State as u32,
+            qos: self.config.qos,
+        };
+
+        vec![s]
+    }
+
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
+        match label.try_into() {
+            Ok(ButtonStateMsgType::State) => {
+                let state = match self.parse_state(&data) {
+                    Some(state) => state,
+                    None => {
+                        self.presence = None;
+                        anyhow::bail!("invalid presence state {}", data);
+                    }
+                };
+                self.presence = Some(if state == self.config.home {
+                    Presence::Home
+                } else if self.config.away.contains(&state) {
+                    Presence::Away
+                } else {
+                    Presence::Zone(state)
+                });
+            }
+
+            _ => error!("Invalid message label {}", label),
+        }
+
+        Ok(())
+    }
+
+    fn process_disconnected(&mut self) {
+        self.presence = None;
+    }
+
+    fn get_display_state(&self) -> DisplayState {
+        match self.presence {
+            None => DisplayState::Unknown,
+            Some(Presence::Home) => DisplayState::On,
+            Some(Presence::Away) => DisplayState::Off,
+            Some(Presence::Zone(_)) => DisplayState::OnOther,
+        }
+    }
+
+    fn get_extra(&self) -> Extra {
+        let text = match &self.presence {
+            Some(Presence::Home) => "Home",
+            Some(Presence::Away) => "Away",
+            Some(Presence::Zone(zone)) => zone,
+            None => "--",
+        };
+        Extra::Status(text.to_string())
+    }
+
+    /// Only shows where someone is, there is nothing to do.
+    fn get_press_commands(&self) -> Vec<Command> {
+        vec![]
+    }
+
+    fn get_icon(&self) -> Icon {
+        match self.presence {
+            Some(Presence::Home) => Icon::Home,
+            _ => Icon::Away,
+        }
+    }
+
+    fn get_name(&self) -> String {
+        self.config.name.clone()
+    }
+}
+
+enum ButtonStateMsgType {
+    State,
+}
+
+impl TryFrom<u32> for ButtonStateMsgType {
+    type Error = ();
+
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ButtonStateMsgType::State as u32 => Ok(ButtonStateMsgType::State),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATE: u32 = ButtonStateMsgType::State as u32;
+
+    fn presence(extra: &str) -> PresenceController {
+        let config = serde_json::from_str(&format!(
+            r#"{{"name": "Sam", "topic": "presence/sam" {}}}"#,
+            extra
+        ))
+        .unwrap();
+        PresenceController::new(&config)
+    }
+
+    fn status(text: &str) -> Extra {
+        Extra::Status(text.to_string())
+    }
+
+    #[test]
+    fn home_away_and_zones() {
+        let mut presence = presence("");
+        assert_eq!(presence.get_display_state(), DisplayState::Unknown);
+        assert_eq!(presence.get_extra(), status("--"));
+
+        for (data, state, extra) in [
+            ("home", DisplayState::On, "Home"),
+            ("not_home", DisplayState::Off, "Away"),
+            ("away\n", DisplayState::Off, "Away"),
+            ("Work", DisplayState::OnOther, "Work"),
+        ] {
+            presence.process_message(STATE, data.to_string()).unwrap();
+            assert_eq!(presence.get_display_state(), state);
+            assert_eq!(presence.get_extra(), status(extra));
+        }
+    }
+
+    #[test]
+    fn state_from_a_json_field() {
+        let mut presence = presence(r#", "field": "state", "home": "Home", "away": ["Out"]"#);
+        let data = r#"{"state": "Home", "battery": 50}"#;
+        presence.process_message(STATE, data.to_string()).unwrap();
+        assert_eq!(presence.get_extra(), status("Home"));
+
+        let data = r#"{"state": "Out"}"#;
+        presence.process_message(STATE, data.to_string()).unwrap();
+        assert_eq!(presence.get_display_state(), DisplayState::Off);
+    }
+
+    #[test]
+    fn unusable_state_clears_the_presence() {
+        let mut presence = presence(r#", "field": "state""#);
+        for data in ["home", r#"{"state": ""}"#, r#"{"state": 1}"#, "{}"] {
+            let data = data.to_string();
+            presence
+                .process_message(STATE, r#"{"state": "home"}"#.to_string())
+                .unwrap();
+            assert!(presence.process_message(STATE, data).is_err());
+            assert_eq!(presence.get_extra(), status("--"));
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use serde::Deserialize;
+
+use crate::button_controllers::*;
+
+#[derive(Clone, Deserialize)]
+pub struct ScenesConfig {
+    #[serde(flatten)]
+    pub c: CommonConfig,
+    pub scenes: Vec<String>,
+    pub priority: Priority,
+}
+
+impl Config for ScenesConfig {
+    fn create_controller(&self) -> Box<dyn Controller> {
+        Box::new(ScenesController::new(self))
+    }
+}
+
+pub struct ScenesController {
+    config: ScenesConfig,
+    power: Option<String>,
+    scenes: Option<Vec<String>>,
+}
+
+impl ScenesController {
+    pub fn new(config: &ScenesConfig) -> Self {
+        Self {
+            config: config.clone(),
+            power: None,
+            scenes: None,
+        }
+    }
+
+    /// Index of the first of our scenes that is active.
+    fn get_active(&self) -> Option<usize> {
+        let scenes = self.scenes.as_ref()?;
+        self.config
+            .scenes
+            .iter()
+            .position(|scene| scenes.contains(scene))
+    }
+
+    /// The scene to switch to on the next press, or `None` to turn off. After
+    /// the last scene toggle turns the light off, and turn_on starts again at
+    /// the first.
+    fn get_next(&self) -> Option<&String> {
+        let next = match self.get_active() {
+            Some(index) => index + 1,
+            None => 0,
+        };
+
+        match (&self.config.c.action, self.config.scenes.get(next)) {
+            (Action::TurnOff, _) => None,
+            (_, Some(scene)) => Some(scene),
+            (Action::TurnOn, None) => self.config.scenes.first(),
+            (Action::Toggle, None) => None,
+        }
+    }
+}
+
+fn topic(parts: &[&str]) -> String {
+    parts.join("/")
+}
+
+impl Controller for ScenesController {
+    fn get_subscriptions(&self) -> Vec<Subscription> {
+        let mut result: Vec<Subscription> = Vec::new();
+        let config = &self.config;
+
+        let p = ["state", &config.c.topic_substr, "power"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::Power as u32,
+            qos: config.c.qos,
+        };
+        result.push(s);
+
+        let p = ["state", &config.c.topic_substr, "scenes"];
+        let s = Subscription {
+            topic: topic(&p),
+            label: ButtonStateMsgType::Scenes as u32,
+            qos: config.c.qos,
+        };
+        result.push(s);
+
+        result
+    }
+
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
+        match label.try_into() {
+            Ok(ButtonStateMsgType::Power) => self.power = Some(data),
+
+            Ok(ButtonStateMsgType::Scenes) => match serde_json::from_str(&data) {
+                Ok(scenes) => self.scenes = Some(scenes),
+                Err(e) => error!("Invalid scenes value {}: {}", data, e),
+            },
+
+            _ => error!("Invalid message label {}", label),
+        }
+
+        Ok(())
+    }
+
+    fn process_disconnected(&mut self) {
+        self.power = None;
+        self.scenes = None;
+    }
+
+    fn get_display_state(&self) -> DisplayState {
+        let scenes_empty = match self.scenes.as_deref() {
+            Some(scenes) => scenes.is_empty(),
+            None => true,
+        };
+
+        let state = match self.power.as_deref() {
+            None => DisplayState::Unknown,
+            Some("HARD_OFF") => DisplayState::HardOff,
+            Some("ON") if scenes_empty => DisplayState::OnOther,
+            Some("OFF") if scenes_empty => DisplayState::Off,
+            _ => match (&self.scenes, self.get_active()) {
+                (None, _) => DisplayState::Unknown,
+                (Some(_), Some(_)) => DisplayState::On,
+                (Some(_), None) if !scenes_empty => DisplayState::OnOther,
+                (Some(_), None) => DisplayState::Off,
+            },
+        };
+
+        let action = &self.config.c.action;
+        get_display_state_for_action(state, action)
+    }
+
+    fn get_press_commands(&self) -> Vec<Command> {
+        let message = match self.get_next() {
+            Some(scene) => serde_json::json!({
+                "scene": scene,
+                "priority": self.config.priority,
+            }),
+            None => serde_json::json!({
+                "priority": self.config.priority,
+                "action": "turn_off",
+            }),
+        };
+
+        let topic = format!("command/{}", self.config.c.topic_substr);
+        let command = Command {
+            topic,
+            message,
+            qos: self.config.c.qos,
+            retain: self.config.c.retain,
+            delay: Duration::ZERO,
+        };
+
+        vec![command]
+    }
+
+    fn get_icon(&self) -> Icon {
+        self.config.c.icon.clone()
+    }
+
+    fn get_name(&self) -> String {
+        self.config.c.name.clone()
+    }
+
+    fn get_display_name(&self) -> String {
+        match self.get_active() {
+            Some(index) => self.config.scenes[index].clone(),
+            None => self.get_name(),
+        }
+    }
+}
+
+enum ButtonStateMsgType {
+    Power,
+    Scenes,
+}
+
+impl TryFrom<u32> for ButtonStateMsgType {
+    type Error = ();
+
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ButtonStateMsgType::Power as u32 => Ok(ButtonStateMsgType::Power),
+            x if x == ButtonStateMsgType::Scenes as u32 => Ok(ButtonStateMsgType::Scenes),
+            _ => Err(()),
+        }
+    }
+}
+
+type Priority = i32;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POWER: u32 = ButtonStateMsgType::Power as u32;
+    const SCENES: u32 = ButtonStateMsgType::Scenes as u32;
+
+    fn scenes(action: &str) -> ScenesController {
+        let config = serde_json::from_str(&format!(
+            r#"{{"name": "Lounge", "topic_substr": "Lounge", "action": "{}", "icon": "light",
+                "scenes": ["bright", "dim", "night"], "priority": 100}}"#,
+            action
+        ))
+        .unwrap();
+        ScenesController::new(&config)
+    }
+
+    /// The message a press sends with these scenes active.
+    fn next(controller: &mut ScenesController, active: &str) -> String {
+        controller.process_message(POWER, "ON".to_string()).unwrap();
+        controller
+            .process_message(SCENES, active.to_string())
+            .unwrap();
+        controller.get_press_commands()[0].get_message()
+    }
+
+    fn scene(scene: &str) -> String {
+        format!(r#"{{"priority":100,"scene":"{}"}}"#, scene)
+    }
+
+    const TURN_OFF: &str = r#"{"action":"turn_off","priority":100}"#;
+
+    #[test]
+    fn toggle_cycles_then_turns_off() {
+        let mut controller = scenes("toggle");
+        assert_eq!(next(&mut controller, "[]"), scene("bright"));
+        assert_eq!(controller.get_display_state(), DisplayState::OnOther);
+        assert_eq!(controller.get_display_name(), "Lounge");
+
+        assert_eq!(next(&mut controller, r#"["bright"]"#), scene("dim"));
+        assert_eq!(controller.get_display_state(), DisplayState::On);
+        assert_eq!(controller.get_display_name(), "bright");
+
+        assert_eq!(next(&mut controller, r#"["dim", "other"]"#), scene("night"));
+        assert_eq!(next(&mut controller, r#"["night"]"#), TURN_OFF);
+
+        // Scenes that aren't ours start again at the first.
+        assert_eq!(next(&mut controller, r#"["other"]"#), scene("bright"));
+        assert_eq!(controller.get_display_state(), DisplayState::OnOther);
+    }
+
+    #[test]
+    fn turn_on_wraps_to_the_first_scene() {
+        let mut controller = scenes("turn_on");
+        assert_eq!(next(&mut controller, r#"["dim"]"#), scene("night"));
+        assert_eq!(next(&mut controller, r#"["night"]"#), scene("bright"));
+
+        let mut controller = scenes("turn_off");
+        assert_eq!(next(&mut controller, r#"["dim"]"#), TURN_OFF);
+    }
+}
@@ -0,0 +1,282 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::button_controllers::*;
+
+#[derive(Clone, Deserialize)]
+pub struct SensorConfig {
+    pub name: String,
+    pub topic: String,
+    /// Read the value from this path into a JSON message, otherwise the whole
+    /// message is the value.
+    #[serde(default)]
+    pub field: Option<String>,
+    #[serde(default)]
+    pub unit: String,
+    #[serde(default = "default_decimals")]
+    pub decimals: usize,
+    #[serde(default)]
+    pub thresholds: Vec<Threshold>,
+    #[serde(default)]
+    pub refresh_topic: Option<String>,
+    #[serde(default = "default_refresh_message")]
+    pub refresh_message: Value,
+    #[serde(default)]
+    pub qos: QoS,
+}
+
+/// The first threshold that matches the value sets the state, otherwise it
+/// is `on`.
+#[derive(Clone, Deserialize)]
+pub struct Threshold {
+    #[serde(default)]
+    pub above: Option<f64>,
+    #[serde(default)]
+    pub below: Option<f64>,
+    pub state: DisplayState,
+}
+
+impl Threshold {
+    fn matches(&self, value: f64) -> bool {
+        let above = self.above.map_or(true, |above| value > above);
+        let below = self.below.map_or(true, |below| value < below);
+        above && below
+    }
+}
+
+fn default_decimals() -> usize {
+    1
+}
+
+fn default_refresh_message() -> Value {
+    serde_json::json!({})
+}
+
+impl Config for SensorConfig {
+    fn create_controller(&self) -> Box<dyn Controller> {
+        Box::new(SensorController::new(self))
+    }
+}
+
+pub struct SensorController {
+    config: SensorConfig,
+    value: Option<f64>,
+}
+
+impl SensorController {
+    pub fn new(config: &SensorConfig) -> Self {
+        Self {
+            config: config.clone(),
+            value: None,
+        }
+    }
+
+    fn parse_value(&self, data: &str) -> Option<f64> {
+        let field = match &self.config.field {
+            Some(field) => field,
+            None => return data.trim().parse().ok(),
+        };
+
+        let json: Value = serde_json::from_str(data).ok()?;
+        match get_json_path(&json, field)? {
+            Value::Number(number) => number.as_f64(),
+            Value::String(string) => string.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl Controller for SensorController {
+    fn get_subscriptions(&self) -> Vec<Subscription> {
+        let s = Subscription {
+            topic: self.config.topic.clone(),
+            label: ButtonStateMsgType::Value as u32,
+            qos: self.config.qos,
+        };
+
+        vec![s]
+    }
+
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
+        match label.try_into() {
+            Ok(ButtonStateMsgType::Value) => {
+                // Show "--" rather than a value that is out of date.
+                self.value = self.parse_value(&data);
+                if self.value.is_none() {
+                    anyhow::bail!("invalid sensor value {}", data);
+                }
+            }
+
+            _ => error!("Invalid message label {}", label),
+        }
+
+        Ok(())
+    }
+
+    fn process_disconnected(&mut self) {
+        self.value = None;
+    }
+
+    fn get_display_state(&self) -> DisplayState {
+        let value = match self.value {
+            Some(value) => value,
+            None => return DisplayState::Unknown,
+        };
+
+        match self.config.thresholds.iter().find(|t| t.matches(value)) {
+            Some(threshold) => threshold.state.clone(),
+            None => DisplayState::On,
+        }
+    }
+
+    fn get_extra(&self) -> Extra {
+        let text = match self.value {
+            Some(value) => {
+                format!("{:.*}{}", self.config.decimals, value, self.config.unit)
+            }
+            _ => "--".to_string(),
+        };
+        Extra::Value(text)
+    }
+
+    fn get_press_commands(&self) -> Vec<Command> {
+        let topic = match &self.config.refresh_topic {
+            Some(topic) => topic.clone(),
+            None => return vec![],
+        };
+
+        let command = Command {
+            topic,
+            message: self.config.refresh_message.clone(),
+            qos: self.config.qos,
+            retain: false,
+            delay: Duration::ZERO,
+        };
+
+        vec![command]
+    }
+
+    /// A refresh may well return the same value, so don't wait for a change.
+    fn get_press_pending(&self) -> bool {
+        false
+    }
+
+    /// Not drawn, the value is shown instead.
+    fn get_icon(&self) -> Icon {
+        Icon::Light
+    }
+
+    fn get_name(&self) -> String {
+        self.config.name.clone()
+    }
+}
+
+enum ButtonStateMsgType {
+    Value,
+}
+
+impl TryFrom<u32> for ButtonStateMsgType {
+    type Error = ();
+
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ButtonStateMsgType::Value as u32 => Ok(ButtonStateMsgType::Value),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUE: u32 = ButtonStateMsgType::Value as u32;
+
+    fn sensor(extra: &str) -> SensorController {
+        let config = serde_json::from_str(&format!(
+            r#"{{"name": "Temp", "topic": "state/temp", "unit": "C", {}
+                "thresholds": [{{"below": 10, "state": "off"}},
+                               {{"above": 25, "below": 30, "state": "on_other"}},
+                               {{"above": 30, "state": "error"}}]}}"#,
+            extra
+        ))
+        .unwrap();
+        SensorController::new(&config)
+    }
+
+    fn threshold(json: &str) -> Threshold {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn threshold_matches() {
+        let between = threshold(r#"{"above": 10, "below": 20, "state": "on"}"#);
+        assert!(between.matches(15.0));
+        assert!(!between.matches(10.0));
+        assert!(!between.matches(20.0));
+        assert!(!between.matches(25.0));
+
+        let above = threshold(r#"{"above": 10, "state": "on"}"#);
+        assert!(above.matches(1000.0));
+        assert!(!above.matches(-5.0));
+
+        let any = threshold(r#"{"state": "on"}"#);
+        assert!(any.matches(0.0));
+    }
+
+    #[test]
+    fn first_matching_threshold_sets_the_state() {
+        let mut sensor = sensor("");
+        assert_eq!(sensor.get_display_state(), DisplayState::Unknown);
+        assert_eq!(sensor.get_extra(), Extra::Value("--".to_string()));
+
+        for (data, state) in [
+            ("5", DisplayState::Off),
+            ("20", DisplayState::On),
+            ("27", DisplayState::OnOther),
+            ("35", DisplayState::Error),
+        ] {
+            sensor.process_message(VALUE, data.to_string()).unwrap();
+            assert_eq!(sensor.get_display_state(), state, "value {}", data);
+        }
+    }
+
+    #[test]
+    fn value_is_formatted_with_the_unit() {
+        let mut sensor = sensor(r#""decimals": 2,"#);
+        sensor
+            .process_message(VALUE, " 21.456\n".to_string())
+            .unwrap();
+        assert_eq!(sensor.get_extra(), Extra::Value("21.46C".to_string()));
+    }
+
+    #[test]
+    fn value_from_a_json_field() {
+        let mut sensor = sensor(r#""field": "sensors.1.temperature","#);
+        let data = r#"{"sensors": [{"temperature": 1}, {"temperature": 21.5}]}"#;
+        sensor.process_message(VALUE, data.to_string()).unwrap();
+        assert_eq!(sensor.get_extra(), Extra::Value("21.5C".to_string()));
+
+        let data = r#"{"sensors": [{}, {"temperature": " 12 "}]}"#;
+        sensor.process_message(VALUE, data.to_string()).unwrap();
+        assert_eq!(sensor.get_extra(), Extra::Value("12.0C".to_string()));
+    }
+
+    #[test]
+    fn unusable_value_clears_the_reading() {
+        let mut sensor = sensor(r#""field": "temperature","#);
+        sensor
+            .process_message(VALUE, r#"{"temperature": 20}"#.to_string())
+            .unwrap();
+
+        for data in [
+            "20",
+            r#"{"humidity": 50}"#,
+            r#"{"temperature": "warm"}"#,
+            r#"{"temperature": [20]}"#,
+        ] {
+            assert!(sensor.process_message(VALUE, data.to_string()).is_err());
+            assert_eq!(sensor.get_extra(), Extra::Value("--".to_string()));
+        }
+    }
+}
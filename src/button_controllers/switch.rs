@@ -1,7 +1,10 @@
+use serde::Deserialize;
+
 use crate::button_controllers::*;
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize)]
 pub struct SwitchConfig {
+    #[serde(flatten)]
     pub c: CommonConfig,
 }
 
@@ -23,6 +26,17 @@ impl SwitchController {
             power: None,
         }
     }
+
+    /// The state of the switch itself, before the tile's action is applied.
+    fn get_raw_state(&self) -> DisplayState {
+        match self.power.as_deref() {
+            None => DisplayState::Unknown,
+            Some("HARD_OFF") => DisplayState::HardOff,
+            Some("ON") => DisplayState::On,
+            Some("OFF") => DisplayState::Off,
+            _ => DisplayState::Error,
+        }
+    }
 }
 
 fn topic(parts: &[&str]) -> String {
@@ -38,18 +52,21 @@ impl Controller for SwitchController {
         let s = Subscription {
             topic: topic(&p),
             label: ButtonStateMsgType::Power as u32,
+            qos: config.c.qos,
         };
         result.push(s);
 
         result
     }
 
-    fn process_message(&mut self, label: Label, data: String) {
+    fn process_message(&mut self, label: Label, data: String) -> anyhow::Result<()> {
         match label.try_into() {
             Ok(ButtonStateMsgType::Power) => self.power = Some(data),
 
             _ => error!("Invalid message label {}", label),
         }
+
+        Ok(())
     }
 
     fn process_disconnected(&mut self) {
@@ -57,29 +74,18 @@ impl Controller for SwitchController {
     }
 
     fn get_display_state(&self) -> DisplayState {
-        let power = self.power.as_deref();
-
-        let state = match power {
-            None => DisplayState::Unknown,
-            Some("HARD_OFF") => DisplayState::HardOff,
-            Some("ON") => DisplayState::On,
-            Some("OFF") => DisplayState::Off,
-            _ => DisplayState::Error,
-        };
-
         let action = &self.config.c.action;
-        get_display_state_for_action(state, action)
+        get_display_state_for_action(self.get_raw_state(), action)
     }
 
-    fn get_press_commands(&self) -> Vec<Command> {
+    fn get_action_commands(&self, action: &Action) -> Vec<Command> {
         let mut message = serde_json::json!({});
 
-        match self.config.c.action {
+        match action {
             Action::TurnOn => message["action"] = serde_json::json!("turn_on"),
             Action::TurnOff => message["action"] = serde_json::json!("turn_off"),
             Action::Toggle => {
-                let display_state = self.get_display_state();
-                if let DisplayState::On = display_state {
+                if let DisplayState::On = self.get_raw_state() {
                     message["action"] = serde_json::json!("turn_off");
                 } else {
                     message["action"] = serde_json::json!("turn_on");
@@ -88,11 +94,21 @@ impl Controller for SwitchController {
         };
 
         let topic = format!("command/{}", self.config.c.topic_substr);
-        let command = Command { topic, message };
+        let command = Command {
+            topic,
+            message,
+            qos: self.config.c.qos,
+            retain: self.config.c.retain,
+            delay: Duration::ZERO,
+        };
 
         vec![command]
     }
 
+    fn get_press_commands(&self) -> Vec<Command> {
+        self.get_action_commands(&self.config.c.action)
+    }
+
     fn get_icon(&self) -> Icon {
         self.config.c.icon.clone()
     }
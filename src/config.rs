@@ -1,71 +1,686 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::button_controllers::climate::ClimateConfig;
+use crate::button_controllers::countdown::CountdownConfig;
+use crate::button_controllers::cover::CoverConfig;
+use crate::button_controllers::dimmer::DimmerConfig;
+use crate::button_controllers::generic::GenericConfig;
+use crate::button_controllers::group::GroupConfig;
 use crate::button_controllers::lights::LightConfig;
+use crate::button_controllers::lock::LockConfig;
+use crate::button_controllers::macros::MacroConfig;
 use crate::button_controllers::music::MusicConfig;
+use crate::button_controllers::presence::PresenceConfig;
+use crate::button_controllers::scenes::ScenesConfig;
+use crate::button_controllers::sensor::SensorConfig;
 use crate::button_controllers::switch::SwitchConfig;
-use crate::button_controllers::Action;
 use crate::button_controllers::CommonConfig;
 use crate::button_controllers::Config;
-use crate::button_controllers::Icon;
-
-pub const NUM_CONTROLLERS: usize = 6;
-pub const NIGHT_TOPIC: &str = "state/Brian/Night/power";
-pub const NIGHT_CONTROLLER: usize = 0;
-
-pub fn get_controllers_config() -> [Box<dyn Config>; NUM_CONTROLLERS] {
-    [
-        Box::new(LightConfig {
-            c: CommonConfig {
-                name: "Brian Auto".to_string(),
-                topic_substr: "Brian/Light".to_string(),
-                action: Action::Toggle,
-                icon: Icon::Light,
-            },
-            scene: "auto".to_string(),
-            priority: 100,
-        }),
-        Box::new(LightConfig {
-            c: CommonConfig {
-                name: "Brian On".to_string(),
-                topic_substr: "Brian/Light".to_string(),
-                action: Action::Toggle,
-                icon: Icon::Light,
-            },
-            scene: "default".to_string(),
-            priority: 100,
-        }),
-        Box::new(SwitchConfig {
-            c: CommonConfig {
-                name: "Brian Fan".to_string(),
-                topic_substr: "Brian/Fan".to_string(),
-                action: Action::Toggle,
-                icon: Icon::Fan,
-            },
-        }),
-        Box::new(LightConfig {
-            c: CommonConfig {
-                name: "Passage".to_string(),
-                topic_substr: "Passage/Light".to_string(),
-                action: Action::Toggle,
-                icon: Icon::Light,
-            },
-            scene: "default".to_string(),
-            priority: 100,
-        }),
-        Box::new(MusicConfig {
-            c: CommonConfig {
-                name: "Brian Wake-Up".to_string(),
-                topic_substr: "Brian/Robotica".to_string(),
-                action: Action::Toggle,
-                icon: Icon::WakeUp,
-            },
-            play_list: "wake_up".to_string(),
-        }),
-        Box::new(SwitchConfig {
-            c: CommonConfig {
-                name: "TV".to_string(),
-                topic_substr: "Dining/TvSwitch".to_string(),
-                action: Action::Toggle,
-                icon: Icon::TV,
-            },
-        }),
-    ]
+use crate::button_controllers::PressConfig;
+use crate::mqtt::ReconnectPolicy;
+
+pub const DEFAULT_CONFIG: &str = include_str!("config.json");
+
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024;
+const DEFAULT_COMMAND_TIMEOUT: u64 = 10;
+
+pub struct RemoteConfig {
+    pub availability_topic: Option<String>,
+    pub max_message_size: Option<usize>,
+    pub command_timeout: Option<u64>,
+    pub reconnect_delay: Option<u64>,
+    pub reconnect_max_delay: Option<u64>,
+    pub reconnect_attempts: Option<u32>,
+    pub night_topic: Option<String>,
+    pub night_controller: Option<usize>,
+    pub controllers: Vec<Box<dyn Config>>,
+    /// Long and double press bindings, one for each controller.
+    pub presses: Vec<PressConfig>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Invalid {
+        field: String,
+        message: String,
+    },
+    Controller {
+        index: usize,
+        field: String,
+        message: String,
+    },
+    NightController {
+        index: usize,
+        num_controllers: usize,
+    },
+}
+
+impl RemoteConfig {
+    pub fn get_availability_topic(&self, id: &str) -> String {
+        match &self.availability_topic {
+            Some(topic) => topic.clone(),
+            None => format!("state/remote/{}/availability", id),
+        }
+    }
+
+    pub fn get_max_message_size(&self) -> usize {
+        self.max_message_size.unwrap_or(DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    pub fn get_command_timeout(&self) -> Duration {
+        Duration::from_secs(self.command_timeout.unwrap_or(DEFAULT_COMMAND_TIMEOUT))
+    }
+
+    pub fn get_reconnect_policy(&self) -> ReconnectPolicy {
+        let mut policy = ReconnectPolicy::default();
+        if let Some(delay) = self.reconnect_delay {
+            policy.initial_delay = Duration::from_secs(delay);
+        }
+        if let Some(delay) = self.reconnect_max_delay {
+            policy.max_delay = Duration::from_secs(delay);
+        }
+        policy.max_attempts = self.reconnect_attempts;
+        policy
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Invalid { field, message } => {
+                write!(f, "invalid config at `{}`: {}", field, message)
+            }
+            ConfigError::Controller {
+                index,
+                field,
+                message,
+            } => write!(
+                f,
+                "invalid controller {} at `{}`: {}",
+                index, field, message
+            ),
+            ConfigError::NightController {
+                index,
+                num_controllers,
+            } => write!(
+                f,
+                "night_controller {} does not exist, there are {} controllers",
+                index, num_controllers
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Deserialize)]
+struct RawConfig {
+    availability_topic: Option<String>,
+    max_message_size: Option<usize>,
+    command_timeout: Option<u64>,
+    reconnect_delay: Option<u64>,
+    reconnect_max_delay: Option<u64>,
+    reconnect_attempts: Option<u32>,
+    night_topic: Option<String>,
+    night_controller: Option<usize>,
+    controllers: Vec<Value>,
+}
+
+fn from_value<T: DeserializeOwned>(index: usize, value: &Value) -> Result<T, ConfigError> {
+    from_value_at(index, "", value)
+}
+
+/// As `from_value`, for a value at `prefix` within the controller.
+fn from_value_at<T: DeserializeOwned>(
+    index: usize,
+    prefix: &str,
+    value: &Value,
+) -> Result<T, ConfigError> {
+    serde_path_to_error::deserialize(value.clone()).map_err(|err| ConfigError::Controller {
+        index,
+        field: get_field(prefix, &err),
+        message: err.inner().to_string(),
+    })
+}
+
+/// The path to the field at fault. serde reports a missing field against
+/// the struct it is missing from, so add the field's name.
+fn get_field(prefix: &str, err: &serde_path_to_error::Error<serde_json::Error>) -> String {
+    let message = err.inner().to_string();
+    let missing = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split('`').next());
+    let path = Some(err.path().to_string()).filter(|path| path != ".");
+
+    let parts: Vec<&str> = [Some(prefix), path.as_deref(), missing]
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.is_empty())
+        .collect();
+    if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join(".")
+    }
+}
+
+/// Structs that flatten the common fields lose track of which of them was
+/// at fault, so check those on their own first.
+fn check_common(
+    index: usize,
+    prefix: &str,
+    controller_type: &str,
+    value: &Value,
+) -> Result<(), ConfigError> {
+    if matches!(
+        controller_type,
+        "light" | "switch" | "music" | "dimmer" | "scenes" | "cover"
+    ) {
+        from_value_at::<CommonConfig>(index, prefix, value)?;
+    }
+    Ok(())
+}
+
+/// Group members are read as a tagged enum, which loses track of the path
+/// within them, so check each one on its own first.
+fn check_members(index: usize, value: &Value) -> Result<(), ConfigError> {
+    let members = match value.get("members") {
+        Some(Value::Array(members)) => members,
+        _ => return Ok(()),
+    };
+
+    for (member_index, member) in members.iter().enumerate() {
+        let prefix = format!("members[{}]", member_index);
+        let member_type = match member.get("type") {
+            Some(Value::String(member_type)) => member_type.as_str(),
+            _ => continue,
+        };
+
+        check_common(index, &prefix, member_type, member)?;
+        match member_type {
+            "switch" => {
+                from_value_at::<SwitchConfig>(index, &prefix, member)?;
+            }
+            "light" => {
+                from_value_at::<LightConfig>(index, &prefix, member)?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Group members only need their `type` and `topic_substr`, and light
+/// members their `scene` and `priority`. The rest of the common fields
+/// aren't used, so fill them in from the group.
+fn with_member_defaults(value: &Value) -> Value {
+    let mut value = value.clone();
+    let defaults = [
+        ("name", value.get("name").cloned()),
+        ("icon", value.get("icon").cloned()),
+        ("action", Some(Value::from("turn_on"))),
+    ];
+
+    if let Some(Value::Array(members)) = value.get_mut("members") {
+        for member in members.iter_mut().filter_map(Value::as_object_mut) {
+            for (key, default) in &defaults {
+                if let Some(default) = default {
+                    member
+                        .entry(key.to_string())
+                        .or_insert_with(|| default.clone());
+                }
+            }
+        }
+    }
+
+    value
+}
+
+fn parse_controller(index: usize, value: &Value) -> Result<Box<dyn Config>, ConfigError> {
+    let controller_type = match value.get("type") {
+        Some(Value::String(controller_type)) => controller_type.as_str(),
+        Some(_) => {
+            return Err(ConfigError::Controller {
+                index,
+                field: "type".to_string(),
+                message: "expected a string".to_string(),
+            })
+        }
+        None => {
+            return Err(ConfigError::Controller {
+                index,
+                field: "type".to_string(),
+                message: "missing field".to_string(),
+            })
+        }
+    };
+
+    check_common(index, "", controller_type, value)?;
+
+    let config: Box<dyn Config> = match controller_type {
+        "light" => Box::new(from_value::<LightConfig>(index, value)?),
+        "switch" => Box::new(from_value::<SwitchConfig>(index, value)?),
+        "music" => {
+            let config = from_value::<MusicConfig>(index, value)?;
+            match (&config.play_list, &config.volume) {
+                (None, None) => {
+                    return Err(ConfigError::Controller {
+                        index,
+                        field: "play_list".to_string(),
+                        message: "missing field, or a volume".to_string(),
+                    })
+                }
+                (Some(_), Some(_)) => {
+                    return Err(ConfigError::Controller {
+                        index,
+                        field: "volume".to_string(),
+                        message: "not allowed with a play_list".to_string(),
+                    })
+                }
+                _ => {}
+            }
+            Box::new(config)
+        }
+        "dimmer" => Box::new(from_value::<DimmerConfig>(index, value)?),
+        "scenes" => Box::new(from_value::<ScenesConfig>(index, value)?),
+        "cover" => Box::new(from_value::<CoverConfig>(index, value)?),
+        "countdown" => {
+            let config = from_value::<CountdownConfig>(index, value)?;
+            if config.increment == 0 {
+                return Err(ConfigError::Controller {
+                    index,
+                    field: "increment".to_string(),
+                    message: "expected an increment above 0".to_string(),
+                });
+            }
+            if matches!(config.max, Some(max) if max < config.increment) {
+                return Err(ConfigError::Controller {
+                    index,
+                    field: "max".to_string(),
+                    message: format!("expected at least increment, {}", config.increment),
+                });
+            }
+            Box::new(config)
+        }
+        "climate" => {
+            let config = from_value::<ClimateConfig>(index, value)?;
+            if config.modes.is_empty() {
+                return Err(ConfigError::Controller {
+                    index,
+                    field: "modes".to_string(),
+                    message: "expected at least one mode".to_string(),
+                });
+            }
+            if config.step <= 0.0 {
+                return Err(ConfigError::Controller {
+                    index,
+                    field: "step".to_string(),
+                    message: "expected a step above 0".to_string(),
+                });
+            }
+            if config.min > config.max {
+                return Err(ConfigError::Controller {
+                    index,
+                    field: "min".to_string(),
+                    message: format!("expected at most max, {}", config.max),
+                });
+            }
+            Box::new(config)
+        }
+        "lock" => Box::new(from_value::<LockConfig>(index, value)?),
+        "sensor" => Box::new(from_value::<SensorConfig>(index, value)?),
+        "presence" => Box::new(from_value::<PresenceConfig>(index, value)?),
+        "macro" => Box::new(from_value::<MacroConfig>(index, value)?),
+        "generic" => Box::new(from_value::<GenericConfig>(index, value)?),
+        "group" => {
+            let value = with_member_defaults(value);
+            check_members(index, &value)?;
+            let config = from_value::<GroupConfig>(index, &value)?;
+            if config.members.is_empty() {
+                return Err(ConfigError::Controller {
+                    index,
+                    field: "members".to_string(),
+                    message: "expected at least one member".to_string(),
+                });
+            }
+            Box::new(config)
+        }
+        _ => {
+            return Err(ConfigError::Controller {
+                index,
+                field: "type".to_string(),
+                message: format!("unknown controller type `{}`", controller_type),
+            })
+        }
+    };
+
+    Ok(config)
+}
+
+pub fn parse_config(json: &str) -> Result<RemoteConfig, ConfigError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(json);
+    let raw: RawConfig =
+        serde_path_to_error::deserialize(deserializer).map_err(|err| ConfigError::Invalid {
+            field: get_field("", &err),
+            message: err.inner().to_string(),
+        })?;
+
+    // Otherwise a remote that can't connect would try again straight away,
+    // forever.
+    if raw.reconnect_delay == Some(0) {
+        return Err(ConfigError::Invalid {
+            field: "reconnect_delay".to_string(),
+            message: "expected a delay above 0".to_string(),
+        });
+    }
+
+    let controllers = raw
+        .controllers
+        .iter()
+        .enumerate()
+        .map(|(index, value)| parse_controller(index, value))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let presses = raw
+        .controllers
+        .iter()
+        .enumerate()
+        .map(|(index, value)| from_value::<PressConfig>(index, value))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(index) = raw.night_controller {
+        if index >= controllers.len() {
+            return Err(ConfigError::NightController {
+                index,
+                num_controllers: controllers.len(),
+            });
+        }
+    }
+
+    Ok(RemoteConfig {
+        availability_topic: raw.availability_topic,
+        max_message_size: raw.max_message_size,
+        command_timeout: raw.command_timeout,
+        reconnect_delay: raw.reconnect_delay,
+        reconnect_max_delay: raw.reconnect_max_delay,
+        reconnect_attempts: raw.reconnect_attempts,
+        night_topic: raw.night_topic,
+        night_controller: raw.night_controller,
+        controllers,
+        presses,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_err(json: &str) -> ConfigError {
+        match parse_config(json) {
+            Ok(_) => panic!("expected {} to be invalid", json),
+            Err(err) => err,
+        }
+    }
+
+    fn assert_controller_err(err: ConfigError, index: usize, field: &str, message: &str) {
+        match err {
+            ConfigError::Controller {
+                index: i,
+                field: f,
+                message: m,
+            } => {
+                assert_eq!(i, index);
+                assert_eq!(f, field);
+                assert!(m.contains(message), "{:?} doesn't contain {:?}", m, message);
+            }
+            err => panic!("expected a controller error, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        let config = parse_config(DEFAULT_CONFIG).unwrap();
+        assert!(!config.controllers.is_empty());
+        assert_eq!(config.presses.len(), config.controllers.len());
+    }
+
+    #[test]
+    fn valid_config() {
+        let config = parse_config(
+            r#"{
+                "night_controller": 1,
+                "command_timeout": 5,
+                "controllers": [
+                    {"type": "light", "name": "Light", "topic_substr": "Light",
+                     "action": "toggle", "icon": "light", "scene": "default", "priority": 100},
+                    {"type": "switch", "name": "Fan", "topic_substr": "Fan",
+                     "action": "turn_on", "icon": "fan", "qos": 1}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.controllers.len(), 2);
+        assert_eq!(config.night_controller, Some(1));
+        assert_eq!(config.get_command_timeout(), Duration::from_secs(5));
+        assert_eq!(config.get_max_message_size(), DEFAULT_MAX_MESSAGE_SIZE);
+        assert_eq!(
+            config.get_availability_topic("abc"),
+            "state/remote/abc/availability"
+        );
+    }
+
+    #[test]
+    fn invalid_top_level_field() {
+        match parse_err(r#"{"controllers": {}}"#) {
+            ConfigError::Invalid { field, .. } => assert_eq!(field, "controllers"),
+            err => panic!("expected an invalid config error, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn unknown_type() {
+        let err = parse_err(
+            r#"{"controllers": [
+                {"type": "switch", "name": "Fan", "topic_substr": "Fan", "action": "toggle", "icon": "fan"},
+                {"type": "toaster", "name": "Toast"}
+            ]}"#,
+        );
+        assert_controller_err(err, 1, "type", "unknown controller type `toaster`");
+    }
+
+    #[test]
+    fn missing_type() {
+        let err = parse_err(r#"{"controllers": [{"name": "Fan"}]}"#);
+        assert_controller_err(err, 0, "type", "missing field");
+    }
+
+    #[test]
+    fn ill_typed_type() {
+        let err = parse_err(r#"{"controllers": [{"type": 1}]}"#);
+        assert_controller_err(err, 0, "type", "expected a string");
+    }
+
+    #[test]
+    fn missing_field() {
+        let err = parse_err(
+            r#"{"controllers": [
+                {"type": "switch", "name": "Fan", "action": "toggle", "icon": "fan"}
+            ]}"#,
+        );
+        assert_controller_err(err, 0, "topic_substr", "missing field `topic_substr`");
+    }
+
+    #[test]
+    fn missing_field_of_each_type() {
+        let types = [
+            "light",
+            "switch",
+            "music",
+            "dimmer",
+            "scenes",
+            "cover",
+            "countdown",
+            "climate",
+            "lock",
+            "sensor",
+            "presence",
+            "macro",
+            "generic",
+            "group",
+        ];
+        for controller_type in types {
+            let json = format!(r#"{{"controllers": [{{"type": "{}"}}]}}"#, controller_type);
+            match parse_err(&json) {
+                ConfigError::Controller { field, message, .. } => assert_eq!(
+                    message,
+                    format!("missing field `{}`", field),
+                    "type {}",
+                    controller_type
+                ),
+                err => panic!("expected a controller error, got {:?}", err),
+            }
+        }
+    }
+
+    #[test]
+    fn missing_top_level_field() {
+        match parse_err("{}") {
+            ConfigError::Invalid { field, .. } => assert_eq!(field, "controllers"),
+            err => panic!("expected an invalid config error, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn nested_field() {
+        let err = parse_err(
+            r#"{"controllers": [
+                {"type": "sensor", "name": "Temp", "topic": "temp", "thresholds": [{"above": 1}]}
+            ]}"#,
+        );
+        assert_controller_err(err, 0, "thresholds[0].state", "missing field `state`");
+    }
+
+    #[test]
+    fn group_member_field() {
+        let err = parse_err(
+            r#"{"controllers": [
+                {"type": "group", "name": "All", "icon": "light", "members": [
+                    {"type": "switch", "topic_substr": "Fan"},
+                    {"type": "switch"}
+                ]}
+            ]}"#,
+        );
+        assert_controller_err(err, 0, "members[1].topic_substr", "missing field");
+
+        let err = parse_err(
+            r#"{"controllers": [
+                {"type": "group", "name": "All", "icon": "light", "members": [
+                    {"type": "light", "topic_substr": "Lamp", "scene": "default", "priority": "high"}
+                ]}
+            ]}"#,
+        );
+        assert_controller_err(err, 0, "members[0].priority", "invalid type");
+
+        let err = parse_err(
+            r#"{"controllers": [
+                {"type": "group", "name": "All", "icon": "light", "members": [
+                    {"type": "switch", "topic_substr": "Fan", "qos": 3}
+                ]}
+            ]}"#,
+        );
+        assert_controller_err(err, 0, "members[0].qos", "");
+    }
+
+    #[test]
+    fn ill_typed_field() {
+        let err = parse_err(
+            r#"{"controllers": [
+                {"type": "switch", "name": "Fan", "topic_substr": "Fan", "action": "toggle", "icon": "fan"},
+                {"type": "switch", "name": "Fan", "topic_substr": "Fan", "action": "toggle", "icon": 5}
+            ]}"#,
+        );
+        assert_controller_err(err, 1, "icon", "invalid type");
+    }
+
+    #[test]
+    fn invalid_qos() {
+        let err = parse_err(
+            r#"{"controllers": [
+                {"type": "switch", "name": "Fan", "topic_substr": "Fan", "action": "toggle", "icon": "fan", "qos": 3}
+            ]}"#,
+        );
+        assert_controller_err(err, 0, "qos", "invalid qos 3");
+    }
+
+    #[test]
+    fn reconnect_policy() {
+        let config = parse_config(r#"{"controllers": []}"#).unwrap();
+        assert_eq!(config.get_reconnect_policy(), ReconnectPolicy::default());
+
+        let config = parse_config(
+            r#"{"reconnect_delay": 2, "reconnect_max_delay": 30, "reconnect_attempts": 5,
+                "controllers": []}"#,
+        )
+        .unwrap();
+        let policy = config.get_reconnect_policy();
+        assert_eq!(policy.initial_delay, Duration::from_secs(2));
+        assert_eq!(policy.max_delay, Duration::from_secs(30));
+        assert_eq!(policy.max_attempts, Some(5));
+
+        match parse_err(r#"{"reconnect_delay": 0, "controllers": []}"#) {
+            ConfigError::Invalid { field, .. } => assert_eq!(field, "reconnect_delay"),
+            err => panic!("expected an invalid config, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn night_controller_out_of_range() {
+        let err = parse_err(
+            r#"{"night_controller": 1, "controllers": [
+                {"type": "switch", "name": "Fan", "topic_substr": "Fan", "action": "toggle", "icon": "fan"}
+            ]}"#,
+        );
+        match err {
+            ConfigError::NightController {
+                index,
+                num_controllers,
+            } => {
+                assert_eq!(index, 1);
+                assert_eq!(num_controllers, 1);
+            }
+            err => panic!("expected a night controller error, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn music_needs_play_list_or_volume() {
+        let music = r#"{"type": "music", "name": "Music", "topic_substr": "Music", "action": "toggle", "icon": "tv""#;
+
+        let err = parse_err(&format!(r#"{{"controllers": [{}}}]}}"#, music));
+        assert_controller_err(err, 0, "play_list", "missing field");
+
+        parse_config(&format!(
+            r#"{{"controllers": [{}, "play_list": "wake_up"}}]}}"#,
+            music
+        ))
+        .unwrap();
+        parse_config(&format!(
+            r#"{{"controllers": [{}, "volume": "up"}}]}}"#,
+            music
+        ))
+        .unwrap();
+
+        let err = parse_err(&format!(
+            r#"{{"controllers": [{}, "play_list": "wake_up", "volume": "up"}}]}}"#,
+            music
+        ));
+        assert_controller_err(err, 0, "volume", "not allowed with a play_list");
+    }
 }
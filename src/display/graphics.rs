@@ -1,26 +1,35 @@
 use std::cmp::max;
 use std::sync::mpsc;
+use std::time::Duration;
 
 use embedded_graphics_framebuf::FrameBuf;
 use log::*;
 
 use embedded_graphics::mono_font::{ascii::FONT_10X20, MonoTextStyle};
 use embedded_graphics::{
-    draw_target::DrawTarget,
+    draw_target::{DrawTarget, DrawTargetExt},
     image::Image,
-    mono_font::ascii::FONT_5X8,
+    mono_font::ascii::{FONT_5X8, FONT_6X10},
     pixelcolor::{Gray8, Rgb555, Rgb888},
     prelude::{ImageDrawable, PixelColor, Point, Primitive, RgbColor, Size},
     primitives::{PrimitiveStyleBuilder, Rectangle},
-    text::{Alignment, Text},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
     Drawable,
 };
 use tinytga::DynamicTga;
 
-use crate::button_controllers::{self, DisplayState, Icon};
+use crate::button_controllers::{self, DisplayState, Extra, Icon, Motion};
+use crate::mqtt::ConnectionState;
 
 use super::DisplayCommand;
 
+const TEXT_WIDTH: u32 = 60;
+const CHAR_WIDTH: u32 = 6;
+const SCROLL_INTERVAL: Duration = Duration::from_millis(150);
+const SCROLL_STEP: u32 = 3;
+const SCROLL_GAP: u32 = 24;
+const ICON_SIZE: u32 = 64;
+
 pub trait FlushableDrawTarget: DrawTarget {
     fn flush(&mut self) -> Result<(), Self::Error>;
     fn set_display_on(&mut self, on: bool) -> Result<(), Self::Error>;
@@ -31,7 +40,50 @@ pub struct State {
     state: DisplayState,
     icon: Icon,
     name: String,
+    extra: Extra,
     pressed: bool,
+    /// Page up and page down adjust this button.
+    focused: bool,
+    /// How far the text has scrolled, in pixels.
+    scroll: u32,
+}
+
+impl State {
+    pub(super) fn new(
+        state: DisplayState,
+        icon: Icon,
+        name: String,
+        extra: Extra,
+        pressed: bool,
+    ) -> Self {
+        State {
+            state,
+            icon,
+            name,
+            extra,
+            pressed,
+            focused: false,
+            scroll: 0,
+        }
+    }
+
+    pub(super) fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    fn is_scrolling(&self) -> bool {
+        matches!(&self.extra, Extra::Text(text) if text_width(text) > TEXT_WIDTH)
+    }
+
+    fn advance_scroll(&mut self) {
+        if let Extra::Text(text) = &self.extra {
+            self.scroll = (self.scroll + SCROLL_STEP) % (text_width(text) + SCROLL_GAP);
+        }
+    }
+}
+
+fn text_width(text: &str) -> u32 {
+    text.chars().count() as u32 * CHAR_WIDTH
 }
 
 pub fn display_thread<D, const NUM_PER_PAGE: usize, const NUM_DISPLAYS: usize>(
@@ -44,6 +96,8 @@ pub fn display_thread<D, const NUM_PER_PAGE: usize, const NUM_DISPLAYS: usize>(
     D::Error: std::fmt::Debug,
 {
     let mut states: Vec<Option<State>> = vec![None; NUM_PER_PAGE];
+    let mut connection = ConnectionState::Connecting;
+    let mut blank = false;
 
     for display in displays.iter_mut() {
         display.set_display_on(true).unwrap();
@@ -51,68 +105,120 @@ pub fn display_thread<D, const NUM_PER_PAGE: usize, const NUM_DISPLAYS: usize>(
         display.flush().unwrap();
     }
 
-    for received in rx {
+    loop {
+        // Only wake up to scroll text if there is text that doesn't fit.
+        let scrolling = !blank && states.iter().flatten().any(|state| state.is_scrolling());
+        let received = if scrolling {
+            match rx.recv_timeout(SCROLL_INTERVAL) {
+                Ok(received) => Some(received),
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match rx.recv() {
+                Ok(received) => Some(received),
+                Err(_) => break,
+            }
+        };
+
         let mut update_components: [bool; NUM_PER_PAGE] = [false; NUM_PER_PAGE];
 
         match received {
-            DisplayCommand::Started => {
+            None => {
+                for (id, state) in states.iter_mut().enumerate() {
+                    if let Some(state) = state {
+                        if state.is_scrolling() {
+                            state.advance_scroll();
+                            update_components[id] = true;
+                        }
+                    }
+                }
+            }
+            Some(DisplayCommand::Started) => {
                 for display in displays.iter_mut() {
                     display.clear(Rgb555::BLUE.into()).unwrap();
                     display.flush().unwrap();
                 }
             }
-            DisplayCommand::DisplayState(state, icon, id, name) => {
-                let pressed = if let Some(old) = &states[id] {
-                    old.pressed
+            Some(DisplayCommand::DisplayState(state, icon, id, name, extra)) => {
+                let (pressed, focused) = if let Some(old) = &states[id] {
+                    (old.pressed, old.focused)
                 } else {
-                    false
+                    (false, false)
+                };
+
+                // Keep scrolling from where it was if the text is the same.
+                let scroll = match &states[id] {
+                    Some(old) if old.extra == extra => old.scroll,
+                    _ => 0,
                 };
 
                 let page = State {
                     state,
                     icon,
                     name,
+                    extra,
                     pressed,
+                    focused,
+                    scroll,
                 };
                 states[id] = Some(page);
                 update_components[id] = true;
             }
-            DisplayCommand::DisplayNone(id) => {
+            Some(DisplayCommand::DisplayNone(id)) => {
                 states[id] = None;
                 update_components[id] = true;
             }
-            DisplayCommand::BlankAll => {
+            Some(DisplayCommand::BlankAll) => {
+                blank = true;
                 for display in displays.iter_mut() {
                     display.set_display_on(false).unwrap();
                 }
             }
-            DisplayCommand::UnBlankAll => {
+            Some(DisplayCommand::UnBlankAll) => {
+                blank = false;
                 for display in displays.iter_mut() {
                     display.set_display_on(true).unwrap();
                 }
                 // update_components = [true; NUM_PER_PAGE];
             }
-            DisplayCommand::ShowPage(_page_num) => {
+            Some(DisplayCommand::ShowPage(_page_num)) => {
                 update_components = [false; NUM_PER_PAGE];
             }
-            DisplayCommand::ButtonPressed(id) => {
+            Some(DisplayCommand::ButtonPressed(id)) => {
                 if let Some(page) = &mut states[id] {
                     page.pressed = true;
                 }
                 update_components[id] = true;
             }
-            DisplayCommand::ButtonReleased(id) => {
+            Some(DisplayCommand::ButtonReleased(id)) => {
                 if let Some(page) = &mut states[id] {
                     page.pressed = false;
                 }
                 update_components[id] = true;
             }
+            Some(DisplayCommand::ButtonFocused(id)) => {
+                if let Some(page) = &mut states[id] {
+                    page.focused = true;
+                }
+                update_components[id] = true;
+            }
+            Some(DisplayCommand::ButtonUnfocused(id)) => {
+                if let Some(page) = &mut states[id] {
+                    page.focused = false;
+                }
+                update_components[id] = true;
+            }
+            Some(DisplayCommand::MqttState(state)) => {
+                connection = state;
+                update_components = [true; NUM_PER_PAGE];
+            }
         }
 
         for (id, component) in components.iter().enumerate() {
             let state = &states[id];
             if update_components[id] {
-                component.draw(displays, state);
+                component.draw(displays, state, &connection);
             }
         }
 
@@ -122,7 +228,7 @@ pub fn display_thread<D, const NUM_PER_PAGE: usize, const NUM_DISPLAYS: usize>(
             }
         }
 
-        info!("Done flushing");
+        debug!("Done flushing");
     }
 }
 pub struct Button {
@@ -138,7 +244,7 @@ impl Button {
         }
     }
 
-    fn draw<D>(&self, displays: &mut [D], state: &Option<State>)
+    fn draw<D>(&self, displays: &mut [D], state: &Option<State>, connection: &ConnectionState)
     where
         D: FlushableDrawTarget,
         D::Color: PixelColor + From<Gray8> + From<Rgb555> + From<Rgb888>,
@@ -151,7 +257,7 @@ impl Button {
             top_left: Point::zero(),
             size: Size::new(128, 64),
         };
-        page_draw(&mut fbuff, state, &bounding_box);
+        page_draw(&mut fbuff, state, connection, &bounding_box);
 
         let display = &mut displays[self.display];
 
@@ -165,8 +271,12 @@ impl Button {
     }
 }
 
-fn page_draw<D>(display: &mut D, state_or_none: &Option<State>, bounding_box: &Rectangle)
-where
+pub(super) fn page_draw<D>(
+    display: &mut D,
+    state_or_none: &Option<State>,
+    connection: &ConnectionState,
+    bounding_box: &Rectangle,
+) where
     D: DrawTarget,
     D::Color: PixelColor + From<Gray8> + From<Rgb555> + From<Rgb888>,
     D::Error: std::fmt::Debug,
@@ -174,11 +284,34 @@ where
     led_clear(display, bounding_box);
 
     if let Some(state) = state_or_none {
-        let image_category = get_image_category(&state.state);
-        let image_data = get_image_data(&image_category, &state.icon);
-        led_draw_image(display, image_data, bounding_box);
-        led_draw_overlay(display, &state.state, bounding_box);
+        if let Extra::Value(value) = &state.extra {
+            led_draw_value(display, value, &state.state, bounding_box);
+        } else if let Extra::Setpoint(current, target) = &state.extra {
+            led_draw_value(display, current, &state.state, bounding_box);
+            led_draw_target(display, target, state.focused, bounding_box);
+        } else if let Extra::Status(status) = &state.extra {
+            let image_category = get_image_category(&state.state);
+            let image_data = get_image_data(&image_category, &state.icon);
+            led_draw_image(display, image_data, bounding_box);
+            led_draw_status(display, status, &state.state, bounding_box);
+        } else {
+            let image_category = get_image_category(&state.state);
+            let image_data = get_image_data(&image_category, &state.icon);
+            led_draw_image(display, image_data, bounding_box);
+        }
+        match &state.extra {
+            Extra::None | Extra::Value(_) | Extra::Setpoint(..) | Extra::Status(_) => {}
+            Extra::Level(level) => led_draw_level(display, *level, bounding_box),
+            Extra::Text(text) => led_draw_text(display, text, state.scroll, bounding_box),
+            Extra::Position(position, motion) => {
+                led_draw_position(display, *position, motion, bounding_box)
+            }
+        }
+        led_draw_overlay(display, &state.state, connection, bounding_box);
         led_draw_name(display, &state.name, bounding_box);
+        if state.focused {
+            led_draw_focused(display, bounding_box);
+        }
         if state.pressed {
             led_draw_pressed(display, bounding_box);
         }
@@ -243,6 +376,24 @@ where
         .unwrap();
 }
 
+fn led_draw_focused<D>(display: &mut D, bounding_box: &Rectangle)
+where
+    D: DrawTarget,
+    D::Color: From<Rgb555>,
+    D::Error: std::fmt::Debug,
+{
+    bounding_box
+        .into_styled(
+            PrimitiveStyleBuilder::new()
+                .reset_fill_color()
+                .stroke_color(Rgb555::YELLOW.into())
+                .stroke_width(2)
+                .build(),
+        )
+        .draw(display)
+        .unwrap();
+}
+
 fn led_draw_name<D>(display: &mut D, name: &str, bounding_box: &Rectangle)
 where
     D: DrawTarget,
@@ -261,6 +412,209 @@ where
     .unwrap();
 }
 
+fn led_draw_level<D>(display: &mut D, level: u8, bounding_box: &Rectangle)
+where
+    D: DrawTarget,
+    D::Color: From<Rgb555>,
+    D::Error: std::fmt::Debug,
+{
+    let ul = Point::new(bounding_box.top_left.x + 2, bounding_box.top_left.y + 4);
+    let size = Size::new(56, 10);
+
+    Rectangle::new(ul, size)
+        .into_styled(
+            PrimitiveStyleBuilder::new()
+                .fill_color(Rgb555::BLACK.into())
+                .stroke_color(Rgb555::WHITE.into())
+                .stroke_width(1)
+                .build(),
+        )
+        .draw(display)
+        .unwrap();
+
+    let width = (size.width - 4) * level.min(100) as u32 / 100;
+    if width > 0 {
+        Rectangle::new(ul + Point::new(2, 2), Size::new(width, size.height - 4))
+            .into_styled(
+                PrimitiveStyleBuilder::new()
+                    .fill_color(Rgb555::YELLOW.into())
+                    .build(),
+            )
+            .draw(display)
+            .unwrap();
+    }
+}
+
+/// Draw a window with the cover filling it from the top, and an arrow while
+/// it moves.
+fn led_draw_position<D>(display: &mut D, position: u8, motion: &Motion, bounding_box: &Rectangle)
+where
+    D: DrawTarget,
+    D::Color: From<Rgb555>,
+    D::Error: std::fmt::Debug,
+{
+    let ul = Point::new(bounding_box.top_left.x + 2, bounding_box.top_left.y + 4);
+    let size = Size::new(20, 40);
+
+    Rectangle::new(ul, size)
+        .into_styled(
+            PrimitiveStyleBuilder::new()
+                .fill_color(Rgb555::BLACK.into())
+                .stroke_color(Rgb555::WHITE.into())
+                .stroke_width(1)
+                .build(),
+        )
+        .draw(display)
+        .unwrap();
+
+    let closed = 100 - position.min(100) as u32;
+    let height = (size.height - 4) * closed / 100;
+    if height > 0 {
+        Rectangle::new(ul + Point::new(2, 2), Size::new(size.width - 4, height))
+            .into_styled(
+                PrimitiveStyleBuilder::new()
+                    .fill_color(Rgb555::YELLOW.into())
+                    .build(),
+            )
+            .draw(display)
+            .unwrap();
+    }
+
+    let arrow = match motion {
+        Motion::Stopped => return,
+        Motion::Opening => "^",
+        Motion::Closing => "v",
+    };
+
+    Text::with_baseline(
+        arrow,
+        ul + Point::new(size.width as i32 + 4, 0),
+        MonoTextStyle::new(&FONT_10X20, Rgb555::WHITE.into()),
+        Baseline::Top,
+    )
+    .draw(display)
+    .unwrap();
+}
+
+fn led_draw_text<D>(display: &mut D, text: &str, scroll: u32, bounding_box: &Rectangle)
+where
+    D: DrawTarget,
+    D::Color: From<Rgb555>,
+    D::Error: std::fmt::Debug,
+{
+    let ul = Point::new(bounding_box.top_left.x + 2, bounding_box.top_left.y + 4);
+    let area = Rectangle::new(ul, Size::new(TEXT_WIDTH, 10));
+    let style = MonoTextStyle::new(&FONT_6X10, Rgb555::WHITE.into());
+    let mut display = display.clipped(&area);
+
+    let x = ul.x - scroll as i32;
+    Text::with_baseline(text, Point::new(x, ul.y), style, Baseline::Top)
+        .draw(&mut display)
+        .unwrap();
+
+    // Draw it again after the gap so the text wraps around as it scrolls.
+    let width = text_width(text);
+    if width > TEXT_WIDTH {
+        let x = x + (width + SCROLL_GAP) as i32;
+        Text::with_baseline(text, Point::new(x, ul.y), style, Baseline::Top)
+            .draw(&mut display)
+            .unwrap();
+    }
+}
+
+fn led_draw_value<D>(display: &mut D, value: &str, state: &DisplayState, bounding_box: &Rectangle)
+where
+    D: DrawTarget,
+    D::Color: From<Rgb555>,
+    D::Error: std::fmt::Debug,
+{
+    let color = match state {
+        DisplayState::On => Rgb555::GREEN,
+        DisplayState::OnOther => Rgb555::YELLOW,
+        DisplayState::Error => Rgb555::RED,
+        _ => Rgb555::WHITE,
+    };
+
+    let x = bounding_box.bottom_right().unwrap().x - 2;
+    let y = bounding_box.top_left.y + 6;
+
+    Text::with_text_style(
+        value,
+        Point::new(x, y),
+        MonoTextStyle::new(&FONT_10X20, color.into()),
+        TextStyleBuilder::new()
+            .alignment(Alignment::Right)
+            .baseline(Baseline::Top)
+            .build(),
+    )
+    .draw(display)
+    .unwrap();
+}
+
+/// Draw the target under the value, marked `+/-` while page up and page
+/// down change it.
+fn led_draw_target<D>(display: &mut D, target: &str, focused: bool, bounding_box: &Rectangle)
+where
+    D: DrawTarget,
+    D::Color: From<Rgb555>,
+    D::Error: std::fmt::Debug,
+{
+    let (text, color) = if focused {
+        (format!("+/- {}", target), Rgb555::YELLOW)
+    } else {
+        (format!("Set {}", target), Rgb555::WHITE)
+    };
+
+    let x = bounding_box.bottom_right().unwrap().x - 2;
+    let y = bounding_box.top_left.y + 28;
+
+    Text::with_text_style(
+        &text,
+        Point::new(x, y),
+        MonoTextStyle::new(&FONT_6X10, color.into()),
+        TextStyleBuilder::new()
+            .alignment(Alignment::Right)
+            .baseline(Baseline::Top)
+            .build(),
+    )
+    .draw(display)
+    .unwrap();
+}
+
+/// Draw the status left of the icon, in the big font if it fits and
+/// otherwise the small one, cut off at the icon.
+fn led_draw_status<D>(display: &mut D, status: &str, state: &DisplayState, bounding_box: &Rectangle)
+where
+    D: DrawTarget,
+    D::Color: From<Rgb555>,
+    D::Error: std::fmt::Debug,
+{
+    let color = match state {
+        DisplayState::On => Rgb555::GREEN,
+        DisplayState::OnOther => Rgb555::YELLOW,
+        DisplayState::Error => Rgb555::RED,
+        _ => Rgb555::WHITE,
+    };
+
+    let ul = Point::new(bounding_box.top_left.x + 2, bounding_box.top_left.y + 6);
+    let width = bounding_box.size.width.saturating_sub(ICON_SIZE + 4);
+    let font = if status.chars().count() as u32 * FONT_10X20.character_size.width <= width {
+        &FONT_10X20
+    } else {
+        &FONT_6X10
+    };
+
+    let area = Rectangle::new(ul, Size::new(width, 20));
+    Text::with_baseline(
+        status,
+        ul,
+        MonoTextStyle::new(font, color.into()),
+        Baseline::Top,
+    )
+    .draw(&mut display.clipped(&area))
+    .unwrap();
+}
+
 enum ImageCategory {
     HardOff,
     On,
@@ -276,6 +630,7 @@ fn get_image_category(state: &DisplayState) -> ImageCategory {
         DisplayState::On => ImageCategory::On,
         DisplayState::Off => ImageCategory::Off,
         DisplayState::OnOther => ImageCategory::OnOther,
+        DisplayState::Pending => ImageCategory::Off,
     }
 }
 
@@ -310,6 +665,24 @@ fn get_image_data<T: PixelColor + From<Gray8> + From<Rgb555> + From<Rgb888>>(
             Off => include_bytes!("images/tv_off_64x64.tga").as_slice(),
             OnOther => include_bytes!("images/tv_on_other_64x64.tga").as_slice(),
         },
+        Icon::Blind => match image {
+            HardOff => include_bytes!("images/blind_hard_off_64x64.tga").as_slice(),
+            On => include_bytes!("images/blind_on_64x64.tga").as_slice(),
+            Off => include_bytes!("images/blind_off_64x64.tga").as_slice(),
+            OnOther => include_bytes!("images/blind_on_other_64x64.tga").as_slice(),
+        },
+        Icon::Home => match image {
+            HardOff => include_bytes!("images/home_hard_off_64x64.tga").as_slice(),
+            On => include_bytes!("images/home_on_64x64.tga").as_slice(),
+            Off => include_bytes!("images/home_off_64x64.tga").as_slice(),
+            OnOther => include_bytes!("images/home_on_other_64x64.tga").as_slice(),
+        },
+        Icon::Away => match image {
+            HardOff => include_bytes!("images/away_hard_off_64x64.tga").as_slice(),
+            On => include_bytes!("images/away_on_64x64.tga").as_slice(),
+            Off => include_bytes!("images/away_off_64x64.tga").as_slice(),
+            OnOther => include_bytes!("images/away_on_other_64x64.tga").as_slice(),
+        },
     };
 
     DynamicTga::from_slice(data).unwrap()
@@ -333,8 +706,12 @@ where
     Image::new(&tga, Point::new(x, y)).draw(display).unwrap();
 }
 
-fn led_draw_overlay<D>(display: &mut D, state: &DisplayState, bounding_box: &Rectangle)
-where
+fn led_draw_overlay<D>(
+    display: &mut D,
+    state: &DisplayState,
+    connection: &ConnectionState,
+    bounding_box: &Rectangle,
+) where
     D: DrawTarget,
     D::Color: From<Rgb555>,
     D::Error: std::fmt::Debug,
@@ -346,11 +723,22 @@ where
         DisplayState::On => "On",
         DisplayState::Off => "Off",
         DisplayState::OnOther => "Other",
+        DisplayState::Pending => "Pending",
+    };
+
+    // While MQTT is down every button is Lost, say why instead.
+    let connection_text = match (state, connection) {
+        (DisplayState::Unknown, ConnectionState::Connecting) => Some("Connecting".to_string()),
+        (DisplayState::Unknown, ConnectionState::Backoff(n)) => {
+            Some(format!("Reconnecting ({})", n))
+        }
+        (DisplayState::Unknown, ConnectionState::Failed) => Some("MQTT failed".to_string()),
+        _ => None,
     };
 
-    if matches!(state, DisplayState::Error | DisplayState::Unknown) {
+    if let Some(connection_text) = connection_text {
         let center = bounding_box.center();
-        let size = Size::new(60, 24);
+        let size = Size::new(104, 24);
 
         let x = center.x - size.width as i32 / 2;
         let y = bounding_box.bottom_right().unwrap().y - 30;
@@ -367,6 +755,41 @@ where
             .draw(display)
             .unwrap();
 
+        Text::with_alignment(
+            &connection_text,
+            Point::new(center.x, y + 15),
+            MonoTextStyle::new(&FONT_6X10, Rgb555::WHITE.into()),
+            Alignment::Center,
+        )
+        .draw(display)
+        .unwrap();
+    } else if matches!(
+        state,
+        DisplayState::Error | DisplayState::Unknown | DisplayState::Pending
+    ) {
+        let (width, stroke_color) = match state {
+            DisplayState::Pending => (80, Rgb555::YELLOW),
+            _ => (60, Rgb555::WHITE),
+        };
+
+        let center = bounding_box.center();
+        let size = Size::new(width, 24);
+
+        let x = center.x - size.width as i32 / 2;
+        let y = bounding_box.bottom_right().unwrap().y - 30;
+        let ul = Point::new(x, y);
+
+        Rectangle::new(ul, size)
+            .into_styled(
+                PrimitiveStyleBuilder::new()
+                    .fill_color(Rgb555::BLACK.into())
+                    .stroke_color(stroke_color.into())
+                    .stroke_width(1)
+                    .build(),
+            )
+            .draw(display)
+            .unwrap();
+
         Text::with_alignment(
             text,
             Point::new(center.x, y + 17),
@@ -0,0 +1,126 @@
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use anyhow::Result;
+
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics_framebuf::FrameBuf;
+use log::*;
+
+use super::graphics::display_thread;
+use super::graphics::Button;
+use super::graphics::FlushableDrawTarget;
+use super::DisplayCommand;
+
+pub const NUM_PER_PAGE: usize = 4;
+pub const NUM_DISPLAYS: usize = 1;
+
+const WIDTH: usize = 128 * 2 + 10 * 3;
+const HEIGHT: usize = 64 * 2 + 10 * 3;
+
+type Buffer = FrameBuf<Rgb888, WIDTH, HEIGHT>;
+
+pub struct Display {
+    buffer: Box<Buffer>,
+    display_on: bool,
+    path: PathBuf,
+}
+
+impl Display {
+    fn new(path: PathBuf) -> Self {
+        Display {
+            buffer: Box::new(FrameBuf([[Rgb888::BLACK; WIDTH]; HEIGHT])),
+            display_on: true,
+            path,
+        }
+    }
+
+    fn write_png(&self) -> Result<()> {
+        let file = File::create(&self.path)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), WIDTH as u32, HEIGHT as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let data: Vec<u8> = self
+            .buffer
+            .0
+            .iter()
+            .flatten()
+            .flat_map(|c| {
+                let c = if self.display_on { *c } else { Rgb888::BLACK };
+                [c.r(), c.g(), c.b()]
+            })
+            .collect();
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Display {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for Display {
+    type Color = Rgb888;
+    type Error = <Buffer as DrawTarget>::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.buffer.draw_iter(pixels)
+    }
+}
+
+impl FlushableDrawTarget for Display {
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if let Err(err) = self.write_png() {
+            error!("Cannot write {}: {}", self.path.display(), err);
+        }
+        Ok(())
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), Self::Error> {
+        self.display_on = on;
+        self.flush()
+    }
+}
+
+fn get_position(column: i32, row: i32) -> Rectangle {
+    let x = 10 + column * (128 + 10);
+    let y = 10 + row * (64 + 10);
+    Rectangle::new(Point::new(x, y), Size::new(128, 64))
+}
+
+pub fn connect() -> Result<mpsc::Sender<DisplayCommand>> {
+    let (tx, rx) = mpsc::channel();
+
+    let path = env::var("HOST_DISPLAY").unwrap_or_else(|_| "host-display.png".to_string());
+    info!("Writing display to {}", path);
+    let display = Display::new(PathBuf::from(path));
+
+    let buttons: [_; NUM_PER_PAGE] = [
+        Button::new(0, get_position(0, 0)),
+        Button::new(0, get_position(1, 0)),
+        Button::new(0, get_position(0, 1)),
+        Button::new(0, get_position(1, 1)),
+    ];
+
+    thread::spawn(move || {
+        let mut displays: [_; NUM_DISPLAYS] = [display];
+
+        display_thread::<_, NUM_PER_PAGE, NUM_DISPLAYS>(&mut displays, &buttons, rx);
+    });
+
+    Ok(tx)
+}
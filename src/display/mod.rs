@@ -1,4 +1,5 @@
 use crate::button_controllers;
+use crate::mqtt::ConnectionState;
 
 #[cfg(feature = "lca2021_badge")]
 pub mod lca2021_badge;
@@ -9,13 +10,22 @@ pub mod makerfab;
 #[cfg(feature = "robotica")]
 pub mod robotica;
 
+#[cfg(feature = "host")]
+pub mod host;
+
 #[cfg(feature = "lca2021_badge")]
 pub mod graphics;
 
 #[cfg(feature = "makerfab")]
 pub mod graphics;
 
-#[derive(Debug)]
+#[cfg(feature = "host")]
+pub mod graphics;
+
+#[cfg(feature = "host")]
+pub mod snapshot;
+
+#[derive(Debug, PartialEq)]
 pub enum DisplayCommand {
     Started,
     DisplayState(
@@ -23,6 +33,7 @@ pub enum DisplayCommand {
         button_controllers::Icon,
         usize,
         String,
+        button_controllers::Extra,
     ),
     DisplayNone(usize),
     BlankAll,
@@ -30,4 +41,7 @@ pub enum DisplayCommand {
     ShowPage(usize),
     ButtonPressed(usize),
     ButtonReleased(usize),
+    ButtonFocused(usize),
+    ButtonUnfocused(usize),
+    MqttState(ConnectionState),
 }
@@ -1,13 +1,28 @@
 use std::sync::mpsc;
 use std::thread;
+use std::time::Duration;
 
 use anyhow::Result;
 use smart_leds::RGB;
 use smart_leds_trait::SmartLedsWrite;
 use ws2812_esp32_rmt_driver::Ws2812Esp32Rmt;
 
+use crate::button_controllers::{Extra, Motion};
+
 use super::DisplayCommand;
 
+const SWEEP_INTERVAL: Duration = Duration::from_millis(200);
+
+fn get_leds(id: usize) -> Option<[usize; 4]> {
+    match id {
+        2 => Some([14, 15, 0, 1]),
+        0 => Some([2, 3, 4, 5]),
+        1 => Some([6, 7, 8, 9]),
+        3 => Some([10, 11, 12, 13]),
+        _ => None,
+    }
+}
+
 fn display_thread(mut leds: Ws2812Esp32Rmt, rx: mpsc::Receiver<DisplayCommand>) {
     let color = RGB::from((1, 1, 1));
     let blank_color = RGB::from((0, 0, 0));
@@ -16,19 +31,48 @@ fn display_thread(mut leds: Ws2812Esp32Rmt, rx: mpsc::Receiver<DisplayCommand>)
     let mut pixels: [RGB<u8>; 16] = [color; 16];
     let blank_pixels: [RGB<u8>; 16] = [blank_color; 16];
 
+    // Moving covers light one LED at a time, going round in the direction
+    // they move.
+    let mut sweeps: [Option<(Motion, RGB<u8>)>; 4] = Default::default();
+    let mut sweep_step: usize = 0;
+
     let iter = pixels.iter().copied();
     leds.write(iter).unwrap();
 
-    for received in rx {
+    loop {
+        let sweeping = !blank && sweeps.iter().any(Option::is_some);
+        let received = if sweeping {
+            match rx.recv_timeout(SWEEP_INTERVAL) {
+                Ok(received) => received,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    sweep_step = sweep_step.wrapping_add(1);
+                    for (id, sweep) in sweeps.iter().enumerate() {
+                        if let (Some((motion, color)), Some(list_leds)) = (sweep, get_leds(id)) {
+                            let lit = match motion {
+                                Motion::Closing => 3 - sweep_step % 4,
+                                _ => sweep_step % 4,
+                            };
+                            for (n, i) in list_leds.into_iter().enumerate() {
+                                pixels[i] = if n == lit { *color } else { blank_color };
+                            }
+                        }
+                    }
+                    let iter = pixels.iter().copied();
+                    leds.write(iter).unwrap();
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match rx.recv() {
+                Ok(received) => received,
+                Err(_) => break,
+            }
+        };
+
         match received {
-            DisplayCommand::DisplayState(state, _icon, id, _name) => {
-                let list_leds_or_none = match id {
-                    2 => Some([14, 15, 0, 1]),
-                    0 => Some([2, 3, 4, 5]),
-                    1 => Some([6, 7, 8, 9]),
-                    3 => Some([10, 11, 12, 13]),
-                    _ => None,
-                };
+            DisplayCommand::DisplayState(state, _icon, id, _name, extra) => {
+                let list_leds_or_none = get_leds(id);
 
                 let color = match state {
                     crate::button_controllers::DisplayState::HardOff => (0, 0, 0),
@@ -37,13 +81,35 @@ fn display_thread(mut leds: Ws2812Esp32Rmt, rx: mpsc::Receiver<DisplayCommand>)
                     crate::button_controllers::DisplayState::On => (0, 1, 0),
                     crate::button_controllers::DisplayState::Off => (0, 0, 1),
                     crate::button_controllers::DisplayState::OnOther => (0, 1, 1),
+                    crate::button_controllers::DisplayState::Pending => (1, 1, 0),
                 };
 
                 let color = RGB::from(color);
 
+                // Show a level or position as the number of lit LEDs,
+                // rounding up so that anything above 0 lights at least one.
+                let num_lit = match &extra {
+                    Extra::Level(level) | Extra::Position(level, _) => {
+                        ((*level).min(100) as usize * 4 + 99) / 100
+                    }
+                    Extra::None
+                    | Extra::Text(_)
+                    | Extra::Value(_)
+                    | Extra::Setpoint(..)
+                    | Extra::Status(_) => 4,
+                };
+
+                if let Some(sweep) = sweeps.get_mut(id) {
+                    *sweep = match extra {
+                        Extra::Position(_, Motion::Stopped) => None,
+                        Extra::Position(_, motion) => Some((motion, color)),
+                        _ => None,
+                    };
+                }
+
                 if let Some(list_leds) = list_leds_or_none {
-                    for i in list_leds {
-                        pixels[i] = color;
+                    for (n, i) in list_leds.into_iter().enumerate() {
+                        pixels[i] = if n < num_lit { color } else { blank_color };
                     }
 
                     if !blank {
@@ -64,9 +130,36 @@ fn display_thread(mut leds: Ws2812Esp32Rmt, rx: mpsc::Receiver<DisplayCommand>)
             }
             DisplayCommand::ButtonPressed(_id) => {}
             DisplayCommand::ButtonReleased(_id) => {}
+            DisplayCommand::ButtonFocused(_id) => {}
+            DisplayCommand::ButtonUnfocused(_id) => {}
             DisplayCommand::Started => {}
-            DisplayCommand::DisplayNone(_) => {}
-            DisplayCommand::ShowPage(_) => {}
+            DisplayCommand::DisplayNone(id) => {
+                if let Some(sweep) = sweeps.get_mut(id) {
+                    *sweep = None;
+                }
+
+                if let Some(list_leds) = get_leds(id) {
+                    for i in list_leds {
+                        pixels[i] = blank_color;
+                    }
+
+                    if !blank {
+                        let iter = pixels.iter().copied();
+                        leds.write(iter).unwrap();
+                    }
+                }
+            }
+            // The new page's tiles follow, until then nothing is shown.
+            DisplayCommand::ShowPage(_) => {
+                sweeps = Default::default();
+                pixels = blank_pixels;
+
+                if !blank {
+                    let iter = pixels.iter().copied();
+                    leds.write(iter).unwrap();
+                }
+            }
+            DisplayCommand::MqttState(_) => {}
         }
     }
 }
@@ -0,0 +1,394 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use embedded_graphics::pixelcolor::{BinaryColor, Rgb555, Rgb888};
+use embedded_graphics::prelude::{Point, RgbColor, Size};
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics_framebuf::FrameBuf;
+use log::*;
+
+use crate::button_controllers::{DisplayState, Extra, Icon, Motion};
+use crate::mqtt::ConnectionState;
+
+use super::graphics::{page_draw, State};
+
+const WIDTH: usize = 128;
+const HEIGHT: usize = 64;
+
+type Buffer = FrameBuf<Rgb555, WIDTH, HEIGHT>;
+
+const SNAPSHOT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/display/snapshots");
+
+const ICONS: &[Icon] = &[
+    Icon::Light,
+    Icon::Fan,
+    Icon::WakeUp,
+    Icon::TV,
+    Icon::Blind,
+    Icon::Home,
+    Icon::Away,
+];
+
+const STATES: &[DisplayState] = &[
+    DisplayState::HardOff,
+    DisplayState::Error,
+    DisplayState::Unknown,
+    DisplayState::On,
+    DisplayState::Off,
+    DisplayState::OnOther,
+    DisplayState::Pending,
+];
+
+fn icon_name(icon: &Icon) -> &'static str {
+    match icon {
+        Icon::Light => "light",
+        Icon::Fan => "fan",
+        Icon::WakeUp => "wake_up",
+        Icon::TV => "tv",
+        Icon::Blind => "blind",
+        Icon::Home => "home",
+        Icon::Away => "away",
+    }
+}
+
+fn state_name(state: &DisplayState) -> &'static str {
+    match state {
+        DisplayState::HardOff => "hard_off",
+        DisplayState::Error => "error",
+        DisplayState::Unknown => "unknown",
+        DisplayState::On => "on",
+        DisplayState::Off => "off",
+        DisplayState::OnOther => "on_other",
+        DisplayState::Pending => "pending",
+    }
+}
+
+struct Image {
+    color_type: png::ColorType,
+    data: Vec<u8>,
+}
+
+fn render(state: &State, connection: &ConnectionState) -> Box<Buffer> {
+    let mut fbuff = Box::new(FrameBuf([[Rgb555::BLACK; WIDTH]; HEIGHT]));
+    let bounding_box = Rectangle::new(Point::zero(), Size::new(WIDTH as u32, HEIGHT as u32));
+    page_draw(
+        fbuff.as_mut(),
+        &Some(state.clone()),
+        connection,
+        &bounding_box,
+    );
+    fbuff
+}
+
+fn to_rgb(fbuff: &Buffer) -> Image {
+    let data = fbuff
+        .0
+        .iter()
+        .flatten()
+        .flat_map(|c| {
+            let c: Rgb888 = (*c).into();
+            [c.r(), c.g(), c.b()]
+        })
+        .collect();
+
+    Image {
+        color_type: png::ColorType::Rgb,
+        data,
+    }
+}
+
+// The SSD1306 displays get the same Rgb555 buffer converted pixel by pixel,
+// see graphics::Button::draw.
+fn to_binary(fbuff: &Buffer) -> Image {
+    let data = fbuff
+        .0
+        .iter()
+        .flatten()
+        .map(|c| match BinaryColor::from(*c) {
+            BinaryColor::On => 0xff,
+            BinaryColor::Off => 0x00,
+        })
+        .collect();
+
+    Image {
+        color_type: png::ColorType::Grayscale,
+        data,
+    }
+}
+
+fn write_png(path: &Path, image: &Image) -> Result<()> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(image.color_type);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.data)?;
+    Ok(())
+}
+
+fn read_png(path: &Path) -> Result<Image> {
+    let decoder = png::Decoder::new(File::open(path)?);
+    let mut reader = decoder.read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    data.truncate(info.buffer_size());
+
+    if info.width != WIDTH as u32 || info.height != HEIGHT as u32 {
+        bail!(
+            "expected {}x{}, got {}x{}",
+            WIDTH,
+            HEIGHT,
+            info.width,
+            info.height
+        );
+    }
+
+    Ok(Image {
+        color_type: info.color_type,
+        data,
+    })
+}
+
+fn check(path: &Path, image: &Image) -> Result<()> {
+    let expected = read_png(path)?;
+
+    if expected.color_type != image.color_type {
+        bail!(
+            "expected {:?} image, got {:?}",
+            expected.color_type,
+            image.color_type
+        );
+    }
+
+    let bytes_per_pixel = image.color_type.samples();
+    let different = expected
+        .data
+        .chunks(bytes_per_pixel)
+        .zip(image.data.chunks(bytes_per_pixel))
+        .filter(|(a, b)| a != b)
+        .count();
+
+    if different > 0 {
+        bail!("{} pixels are different", different);
+    }
+
+    Ok(())
+}
+
+fn actual_path(path: &Path) -> PathBuf {
+    path.with_extension("actual.png")
+}
+
+struct Case {
+    name: String,
+    state: State,
+    connection: ConnectionState,
+}
+
+fn get_cases() -> Vec<Case> {
+    let mut cases = vec![];
+
+    for icon in ICONS {
+        for state in STATES {
+            for pressed in [false, true] {
+                cases.push(Case {
+                    name: format!(
+                        "{}_{}{}",
+                        icon_name(icon),
+                        state_name(state),
+                        if pressed { "_pressed" } else { "" }
+                    ),
+                    state: State::new(
+                        state.clone(),
+                        icon.clone(),
+                        "Name".to_string(),
+                        Extra::None,
+                        pressed,
+                    ),
+                    connection: ConnectionState::Connected,
+                });
+            }
+        }
+
+        cases.push(Case {
+            name: format!("{}_reconnecting", icon_name(icon)),
+            state: State::new(
+                DisplayState::Unknown,
+                icon.clone(),
+                "Name".to_string(),
+                Extra::None,
+                false,
+            ),
+            connection: ConnectionState::Backoff(3),
+        });
+    }
+
+    for level in [0, 1, 50, 100] {
+        let state = if level > 0 {
+            DisplayState::On
+        } else {
+            DisplayState::Off
+        };
+
+        cases.push(Case {
+            name: format!("light_level_{}", level),
+            state: State::new(
+                state,
+                Icon::Light,
+                "Name".to_string(),
+                Extra::Level(level),
+                false,
+            ),
+            connection: ConnectionState::Connected,
+        });
+    }
+
+    for (name, text) in [("short", "Song"), ("long", "A song with a long title")] {
+        cases.push(Case {
+            name: format!("tv_text_{}", name),
+            state: State::new(
+                DisplayState::On,
+                Icon::TV,
+                "Name".to_string(),
+                Extra::Text(text.to_string()),
+                false,
+            ),
+            connection: ConnectionState::Connected,
+        });
+    }
+
+    for (state, value) in [
+        (DisplayState::On, "-18.5C"),
+        (DisplayState::Error, "-2.0C"),
+        (DisplayState::Unknown, "--"),
+    ] {
+        cases.push(Case {
+            name: format!("sensor_{}", state_name(&state)),
+            state: State::new(
+                state,
+                Icon::Light,
+                "Name".to_string(),
+                Extra::Value(value.to_string()),
+                false,
+            ),
+            connection: ConnectionState::Connected,
+        });
+    }
+
+    for (state, position, motion) in [
+        (DisplayState::Off, 0, Motion::Stopped),
+        (DisplayState::OnOther, 30, Motion::Opening),
+        (DisplayState::On, 100, Motion::Stopped),
+    ] {
+        cases.push(Case {
+            name: format!("blind_position_{}", position),
+            state: State::new(
+                state,
+                Icon::Blind,
+                "Name".to_string(),
+                Extra::Position(position, motion),
+                false,
+            ),
+            connection: ConnectionState::Connected,
+        });
+    }
+
+    for (state, current, target, focused) in [
+        (DisplayState::On, "19.5C", "21.0C", false),
+        (DisplayState::Off, "19.5C", "21.0C", true),
+        (DisplayState::Unknown, "--", "--", false),
+    ] {
+        let mut climate = State::new(
+            state.clone(),
+            Icon::Light,
+            "Name".to_string(),
+            Extra::Setpoint(current.to_string(), target.to_string()),
+            false,
+        );
+        climate.set_focused(focused);
+
+        cases.push(Case {
+            name: format!(
+                "climate_{}{}",
+                state_name(&state),
+                if focused { "_focused" } else { "" }
+            ),
+            state: climate,
+            connection: ConnectionState::Connected,
+        });
+    }
+
+    for (name, state, icon, status) in [
+        ("home", DisplayState::On, Icon::Home, "Home"),
+        ("away", DisplayState::Off, Icon::Away, "Away"),
+        ("zone", DisplayState::OnOther, Icon::Away, "Work"),
+        ("zone_long", DisplayState::OnOther, Icon::Away, "Grandma's"),
+        ("unknown", DisplayState::Unknown, Icon::Away, "--"),
+    ] {
+        cases.push(Case {
+            name: format!("presence_{}", name),
+            state: State::new(
+                state,
+                icon,
+                "Name".to_string(),
+                Extra::Status(status.to_string()),
+                false,
+            ),
+            connection: ConnectionState::Connected,
+        });
+    }
+
+    cases
+}
+
+/// Render every icon, state and pressed combination and compare the result
+/// against the reference images in src/display/snapshots. With `regenerate`
+/// the reference images are overwritten instead.
+pub fn run(regenerate: bool) -> Result<()> {
+    let mut failures = 0;
+    let mut total = 0;
+
+    for (kind, convert) in [
+        ("rgb555", to_rgb as fn(&Buffer) -> Image),
+        ("ssd1306", to_binary),
+    ] {
+        let dir = Path::new(SNAPSHOT_DIR).join(kind);
+        std::fs::create_dir_all(&dir)?;
+
+        for case in get_cases() {
+            let path = dir.join(format!("{}.png", case.name));
+            let image = convert(&render(&case.state, &case.connection));
+            total += 1;
+
+            if regenerate {
+                write_png(&path, &image)?;
+                info!("Wrote {}", path.display());
+            } else if let Err(err) = check(&path, &image) {
+                failures += 1;
+                let actual = actual_path(&path);
+                write_png(&actual, &image)?;
+                error!("{}: {}, see {}", path.display(), err, actual.display());
+            } else {
+                let _ = std::fs::remove_file(actual_path(&path));
+            }
+        }
+    }
+
+    if failures > 0 {
+        bail!("{} of {} snapshots do not match", failures, total);
+    }
+
+    info!("{} snapshots ok", total);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn snapshots_match() {
+        super::run(false).unwrap();
+    }
+}
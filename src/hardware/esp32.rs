@@ -1,4 +1,15 @@
-use esp_idf_sys::esp_efuse_mac_get_default;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use embedded_svc::timer::OnceTimer;
+use embedded_svc::timer::Timer as _;
+use embedded_svc::timer::TimerService;
+use esp_idf_svc::timer::EspTimer;
+use esp_idf_svc::timer::EspTimerService;
+use esp_idf_sys::{esp_efuse_mac_get_default, esp_restart};
+
+use crate::app;
 
 pub fn get_unique_id() -> String {
     let mut mac: [u8; 6] = [0; 6];
@@ -8,3 +19,31 @@ pub fn get_unique_id() -> String {
     }
     hex::encode(mac)
 }
+
+pub fn get_mqtt_url() -> String {
+    env!("MQTT_URL").to_string()
+}
+
+pub fn restart() -> ! {
+    unsafe { esp_restart() }
+}
+
+pub struct Timer(EspTimer);
+
+impl Timer {
+    pub fn new(callback: impl FnMut() + Send + 'static) -> Result<Self> {
+        let mut timer_service = EspTimerService::new()?;
+        let timer = timer_service.timer(callback)?;
+        Ok(Timer(timer))
+    }
+}
+
+impl app::Timer for Timer {
+    fn start(&mut self, duration: Duration) {
+        self.0.after(duration).unwrap();
+    }
+
+    fn cancel(&mut self) {
+        self.0.cancel().unwrap();
+    }
+}
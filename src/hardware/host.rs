@@ -0,0 +1,79 @@
+use std::env;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use log::*;
+
+use crate::app;
+
+pub fn get_unique_id() -> String {
+    env::var("REMOTE_ID").unwrap_or_else(|_| "host".to_string())
+}
+
+/// Read when run rather than built, so the tests build without it.
+pub fn get_mqtt_url() -> String {
+    env::var("MQTT_URL").unwrap_or_else(|_| "mqtt://localhost:1883".to_string())
+}
+
+pub fn restart() -> ! {
+    // There is nothing to restart, so exit and let the user start it again.
+    info!("Restart requested, exiting");
+    std::process::exit(0)
+}
+
+enum TimerCommand {
+    Start(Duration),
+    Cancel,
+}
+
+pub struct Timer {
+    tx: mpsc::Sender<TimerCommand>,
+}
+
+impl Timer {
+    pub fn new(mut callback: impl FnMut() + Send + 'static) -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut deadline: Option<Instant> = None;
+
+            loop {
+                let received = match deadline {
+                    Some(deadline) => {
+                        rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    }
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+
+                match received {
+                    Ok(TimerCommand::Start(duration)) => {
+                        deadline = Some(Instant::now() + duration);
+                    }
+                    Ok(TimerCommand::Cancel) => {
+                        deadline = None;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        deadline = None;
+                        callback();
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        Ok(Timer { tx })
+    }
+}
+
+impl app::Timer for Timer {
+    fn start(&mut self, duration: Duration) {
+        self.tx.send(TimerCommand::Start(duration)).unwrap();
+    }
+
+    fn cancel(&mut self) {
+        self.tx.send(TimerCommand::Cancel).unwrap();
+    }
+}
@@ -1 +1,11 @@
+#[cfg(feature = "esp32")]
 pub mod esp32;
+
+#[cfg(feature = "esp32")]
+pub use esp32::{get_mqtt_url, get_unique_id, restart, Timer};
+
+#[cfg(feature = "host")]
+pub mod host;
+
+#[cfg(feature = "host")]
+pub use host::{get_mqtt_url, get_unique_id, restart, Timer};
@@ -0,0 +1,251 @@
+use serde_json::{json, Value};
+
+use crate::button_controllers::{Controller, Press};
+
+const DISCOVERY_PREFIX: &str = "homeassistant";
+
+pub struct Discovery {
+    pub topic: String,
+    pub payload: String,
+}
+
+pub struct HomeAssistant {
+    id: String,
+    availability_topic: String,
+}
+
+impl HomeAssistant {
+    pub fn new(id: &str, availability_topic: &str) -> Self {
+        HomeAssistant {
+            id: id.to_string(),
+            availability_topic: availability_topic.to_string(),
+        }
+    }
+
+    fn object_id(&self) -> String {
+        format!("robotica_remote_{}", self.id)
+    }
+
+    fn config_topic(&self, component: &str, entity: &str) -> String {
+        format!(
+            "{}/{}/{}/{}/config",
+            DISCOVERY_PREFIX,
+            component,
+            self.object_id(),
+            entity
+        )
+    }
+
+    fn device(&self) -> Value {
+        json!({
+            "identifiers": [self.object_id()],
+            "name": format!("Robotica Remote {}", self.id),
+            "manufacturer": "Robotica",
+            "model": "Robotica Remote",
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        })
+    }
+
+    fn discovery(&self, component: &str, entity: &str, mut config: Value) -> Discovery {
+        config["unique_id"] = json!(format!("{}_{}", self.object_id(), entity));
+        config["availability_topic"] = json!(self.availability_topic);
+        config["device"] = self.device();
+        Discovery {
+            topic: self.config_topic(component, entity),
+            payload: config.to_string(),
+        }
+    }
+
+    pub fn page_topic(&self) -> String {
+        format!("state/remote/{}/page", self.id)
+    }
+
+    pub fn display_topic(&self) -> String {
+        format!("state/remote/{}/display", self.id)
+    }
+
+    pub fn night_topic(&self) -> String {
+        format!("state/remote/{}/night", self.id)
+    }
+
+    pub fn event_topic(&self, index: usize) -> String {
+        format!("state/remote/{}/button/{}", self.id, index)
+    }
+
+    pub fn get_discovery(&self, controllers: &[Box<dyn Controller>]) -> Vec<Discovery> {
+        let mut discovery = vec![
+            self.discovery(
+                "sensor",
+                "page",
+                json!({
+                    "name": "Page",
+                    "state_topic": self.page_topic(),
+                    "icon": "mdi:book-open-page-variant",
+                }),
+            ),
+            self.discovery(
+                "sensor",
+                "display",
+                json!({
+                    "name": "Display",
+                    "state_topic": self.display_topic(),
+                    "icon": "mdi:monitor",
+                }),
+            ),
+            self.discovery(
+                "binary_sensor",
+                "night",
+                json!({
+                    "name": "Night",
+                    "state_topic": self.night_topic(),
+                    "payload_on": "ON",
+                    "payload_off": "OFF",
+                    "icon": "mdi:weather-night",
+                }),
+            ),
+        ];
+
+        for (index, controller) in controllers.iter().enumerate() {
+            discovery.push(self.discovery(
+                "event",
+                &format!("button_{}", index),
+                json!({
+                    "name": controller.get_name(),
+                    "state_topic": self.event_topic(index),
+                    "event_types": ["press", "long_press", "double_press"],
+                    "device_class": "button",
+                }),
+            ));
+        }
+
+        discovery
+    }
+
+    /// An empty retained payload removes the entity from Home Assistant.
+    pub fn get_event_removal(&self, index: usize) -> Discovery {
+        Discovery {
+            topic: self.config_topic("event", &format!("button_{}", index)),
+            payload: String::new(),
+        }
+    }
+
+    pub fn get_press_event(&self, press: Press) -> String {
+        let event_type = match press {
+            Press::Short => "press",
+            Press::Long => "long_press",
+            Press::Double => "double_press",
+        };
+        json!({ "event_type": event_type }).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::button_controllers::lights::LightConfig;
+    use crate::button_controllers::Config;
+
+    const AVAILABILITY: &str = "state/remote/abc/availability";
+
+    fn lamp() -> Box<dyn Controller> {
+        let config: LightConfig = serde_json::from_str(
+            r#"{"name": "Lamp", "topic_substr": "Lamp", "action": "toggle", "icon": "light",
+                "scene": "default", "priority": 100}"#,
+        )
+        .unwrap();
+        config.create_controller()
+    }
+
+    fn discovery() -> Vec<(String, Value)> {
+        HomeAssistant::new("abc", AVAILABILITY)
+            .get_discovery(&[lamp()])
+            .into_iter()
+            .map(|d| (d.topic, serde_json::from_str(&d.payload).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn config_topics() {
+        let topics: Vec<String> = discovery().into_iter().map(|(topic, _)| topic).collect();
+        assert_eq!(
+            topics,
+            [
+                "homeassistant/sensor/robotica_remote_abc/page/config",
+                "homeassistant/sensor/robotica_remote_abc/display/config",
+                "homeassistant/binary_sensor/robotica_remote_abc/night/config",
+                "homeassistant/event/robotica_remote_abc/button_0/config",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_entity_has_a_unique_id_and_availability() {
+        let discovery = discovery();
+        let unique_ids: Vec<&str> = discovery
+            .iter()
+            .map(|(_, config)| config["unique_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            unique_ids,
+            [
+                "robotica_remote_abc_page",
+                "robotica_remote_abc_display",
+                "robotica_remote_abc_night",
+                "robotica_remote_abc_button_0",
+            ]
+        );
+        for (_, config) in &discovery {
+            assert_eq!(config["availability_topic"], AVAILABILITY);
+            assert!(config["state_topic"].is_string());
+            assert!(config["name"].is_string());
+        }
+    }
+
+    #[test]
+    fn every_entity_belongs_to_the_device() {
+        for (_, config) in discovery() {
+            assert_eq!(
+                config["device"],
+                json!({
+                    "identifiers": ["robotica_remote_abc"],
+                    "name": "Robotica Remote abc",
+                    "manufacturer": "Robotica",
+                    "model": "Robotica Remote",
+                    "sw_version": env!("CARGO_PKG_VERSION"),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn button_event() {
+        let (_, config) = discovery().pop().unwrap();
+        assert_eq!(config["name"], "Lamp");
+        assert_eq!(config["state_topic"], "state/remote/abc/button/0");
+        assert_eq!(
+            config["event_types"],
+            json!(["press", "long_press", "double_press"])
+        );
+        assert_eq!(config["device_class"], "button");
+
+        let ha = HomeAssistant::new("abc", AVAILABILITY);
+        assert_eq!(
+            ha.get_press_event(Press::Long),
+            r#"{"event_type":"long_press"}"#
+        );
+        let removal = ha.get_event_removal(0);
+        assert_eq!(
+            removal.topic,
+            "homeassistant/event/robotica_remote_abc/button_0/config"
+        );
+        assert!(removal.payload.is_empty());
+    }
+
+    #[test]
+    fn night_sensor_payloads() {
+        let (_, config) = &discovery()[2];
+        assert_eq!(config["state_topic"], "state/remote/abc/night");
+        assert_eq!(config["payload_on"], "ON");
+        assert_eq!(config["payload_off"], "OFF");
+    }
+}
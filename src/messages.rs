@@ -5,12 +5,19 @@ use crate::mqtt;
 pub enum Message {
     MqttConnect,
     MqttDisconnect,
-    MqttReceived(String, String, mqtt::Label),
+    MqttState(mqtt::ConnectionState),
+    MqttPublishFailed(String),
+    MqttReceived(String, Vec<u8>, mqtt::Label),
     #[allow(dead_code)]
     ButtonPress(button::ButtonId),
     #[allow(dead_code)]
     ButtonRelease(button::ButtonId),
+    /// Sent after the press and release, see `button::gestures`.
+    ShortPress(button::ButtonId),
+    LongPress(button::ButtonId),
+    DoublePress(button::ButtonId),
     BlankDisplays,
+    Tick,
 }
 
 pub type Sender = std::sync::mpsc::Sender<messages::Message>;
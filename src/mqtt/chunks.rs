@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ChunkError {
+    TooLarge {
+        topic: String,
+        size: usize,
+        max_size: usize,
+    },
+    Interrupted {
+        topic: String,
+        received: usize,
+        size: usize,
+    },
+    Unexpected {
+        id: u32,
+        offset: usize,
+    },
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkError::TooLarge {
+                topic,
+                size,
+                max_size,
+            } => write!(
+                f,
+                "message on {} is {} bytes, the maximum is {}",
+                topic, size, max_size
+            ),
+            ChunkError::Interrupted {
+                topic,
+                received,
+                size,
+            } => write!(
+                f,
+                "message on {} interrupted after {} of {} bytes",
+                topic, received, size
+            ),
+            ChunkError::Unexpected { id, offset } => {
+                write!(f, "unexpected chunk at offset {} of message {}", offset, id)
+            }
+        }
+    }
+}
+
+pub enum Outcome {
+    Pending,
+    Complete(String, Vec<u8>),
+}
+
+struct Transfer {
+    topic: String,
+    size: usize,
+    data: Vec<u8>,
+}
+
+impl Transfer {
+    fn interrupted(self) -> ChunkError {
+        ChunkError::Interrupted {
+            topic: self.topic,
+            received: self.data.len(),
+            size: self.size,
+        }
+    }
+}
+
+/// Reassembles messages that the MQTT client delivers in several chunks.
+pub struct Reassembler {
+    max_size: usize,
+    transfers: HashMap<u32, Transfer>,
+    /// Sizes of the messages that were too large, so the rest of their
+    /// chunks can be skipped quietly.
+    skipping: HashMap<u32, usize>,
+}
+
+impl Reassembler {
+    pub fn new(max_size: usize) -> Self {
+        Reassembler {
+            max_size,
+            transfers: HashMap::new(),
+            skipping: HashMap::new(),
+        }
+    }
+
+    fn check_complete(&mut self, id: u32) -> Outcome {
+        match self.transfers.get(&id) {
+            Some(transfer) if transfer.data.len() >= transfer.size => {
+                let transfer = self.transfers.remove(&id).unwrap();
+                Outcome::Complete(transfer.topic, transfer.data)
+            }
+            _ => Outcome::Pending,
+        }
+    }
+
+    /// Start a new message. If a message with the same id was still being
+    /// received it is reported as interrupted, and the new one is started
+    /// anyway.
+    pub fn initial(
+        &mut self,
+        id: u32,
+        topic: &str,
+        size: usize,
+        data: &[u8],
+    ) -> Result<Outcome, ChunkError> {
+        let old = self.transfers.remove(&id);
+        self.skipping.remove(&id);
+
+        if size > self.max_size {
+            self.skipping.insert(id, size);
+            return Err(ChunkError::TooLarge {
+                topic: topic.to_string(),
+                size,
+                max_size: self.max_size,
+            });
+        }
+
+        let mut buffer = Vec::with_capacity(size);
+        buffer.extend_from_slice(data);
+        self.transfers.insert(
+            id,
+            Transfer {
+                topic: topic.to_string(),
+                size,
+                data: buffer,
+            },
+        );
+
+        match old {
+            Some(old) => Err(old.interrupted()),
+            None => Ok(self.check_complete(id)),
+        }
+    }
+
+    pub fn subsequent(
+        &mut self,
+        id: u32,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<Outcome, ChunkError> {
+        if let Some(size) = self.skipping.get(&id) {
+            if offset + data.len() >= *size {
+                self.skipping.remove(&id);
+            }
+            return Ok(Outcome::Pending);
+        }
+
+        let transfer = match self.transfers.get_mut(&id) {
+            Some(transfer) => transfer,
+            None => return Err(ChunkError::Unexpected { id, offset }),
+        };
+
+        if offset != transfer.data.len() || offset + data.len() > transfer.size {
+            let transfer = self.transfers.remove(&id).unwrap();
+            return Err(transfer.interrupted());
+        }
+
+        transfer.data.extend_from_slice(data);
+        Ok(self.check_complete(id))
+    }
+
+    /// Drop every incomplete message, e.g. after a disconnect.
+    pub fn interrupt(&mut self) -> Vec<ChunkError> {
+        self.skipping.clear();
+        self.transfers
+            .drain()
+            .map(|(_, transfer)| transfer.interrupted())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(outcome: Result<Outcome, ChunkError>) -> (String, Vec<u8>) {
+        match outcome {
+            Ok(Outcome::Complete(topic, data)) => (topic, data),
+            Ok(Outcome::Pending) => panic!("expected a complete message, still pending"),
+            Err(err) => panic!("expected a complete message, got {}", err),
+        }
+    }
+
+    fn pending(outcome: Result<Outcome, ChunkError>) {
+        match outcome {
+            Ok(Outcome::Pending) => {}
+            Ok(Outcome::Complete(topic, _)) => panic!("unexpected complete message on {}", topic),
+            Err(err) => panic!("expected a pending message, got {}", err),
+        }
+    }
+
+    fn error(outcome: Result<Outcome, ChunkError>) -> ChunkError {
+        match outcome {
+            Err(err) => err,
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn single_chunk() {
+        let mut r = Reassembler::new(16);
+        let (topic, data) = complete(r.initial(1, "a", 5, b"hello"));
+        assert_eq!(topic, "a");
+        assert_eq!(data, b"hello");
+    }
+
+    #[test]
+    fn several_chunks() {
+        let mut r = Reassembler::new(16);
+        pending(r.initial(1, "a", 11, b"hel"));
+        pending(r.subsequent(1, 3, b"lo "));
+        let (topic, data) = complete(r.subsequent(1, 6, b"world"));
+        assert_eq!(topic, "a");
+        assert_eq!(data, b"hello world");
+    }
+
+    #[test]
+    fn interleaved_messages() {
+        let mut r = Reassembler::new(16);
+        pending(r.initial(1, "a", 4, b"ab"));
+        pending(r.initial(2, "b", 4, b"xy"));
+        assert_eq!(complete(r.subsequent(2, 2, b"zz")).1, b"xyzz");
+        assert_eq!(complete(r.subsequent(1, 2, b"cd")).1, b"abcd");
+    }
+
+    #[test]
+    fn out_of_order_chunk() {
+        let mut r = Reassembler::new(16);
+        pending(r.initial(1, "a", 9, b"abc"));
+        assert!(matches!(
+            error(r.subsequent(1, 6, b"ghi")),
+            ChunkError::Interrupted {
+                received: 3,
+                size: 9,
+                ..
+            }
+        ));
+
+        // The message was dropped.
+        assert!(matches!(
+            error(r.subsequent(1, 3, b"def")),
+            ChunkError::Unexpected { id: 1, offset: 3 }
+        ));
+    }
+
+    #[test]
+    fn duplicate_chunk() {
+        let mut r = Reassembler::new(16);
+        pending(r.initial(1, "a", 9, b"abc"));
+        pending(r.subsequent(1, 3, b"def"));
+        assert!(matches!(
+            error(r.subsequent(1, 3, b"def")),
+            ChunkError::Interrupted { received: 6, .. }
+        ));
+    }
+
+    #[test]
+    fn duplicate_initial_chunk() {
+        let mut r = Reassembler::new(16);
+        pending(r.initial(1, "a", 6, b"abc"));
+        assert!(matches!(
+            error(r.initial(1, "a", 6, b"abc")),
+            ChunkError::Interrupted { received: 3, .. }
+        ));
+
+        // The new message carries on.
+        assert_eq!(complete(r.subsequent(1, 3, b"def")).1, b"abcdef");
+    }
+
+    #[test]
+    fn missing_chunk() {
+        let mut r = Reassembler::new(16);
+        pending(r.initial(1, "a", 9, b"abc"));
+        pending(r.initial(2, "b", 9, b"xyz"));
+        pending(r.subsequent(1, 3, b"def"));
+
+        let mut errors = r.interrupt();
+        errors.sort_by_key(|err| err.to_string());
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].to_string(),
+            "message on a interrupted after 6 of 9 bytes"
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "message on b interrupted after 3 of 9 bytes"
+        );
+
+        assert!(matches!(
+            error(r.subsequent(1, 6, b"ghi")),
+            ChunkError::Unexpected { .. }
+        ));
+    }
+
+    #[test]
+    fn chunk_past_the_end() {
+        let mut r = Reassembler::new(16);
+        pending(r.initial(1, "a", 4, b"ab"));
+        assert!(matches!(
+            error(r.subsequent(1, 2, b"cde")),
+            ChunkError::Interrupted { .. }
+        ));
+    }
+
+    #[test]
+    fn oversized_message() {
+        let mut r = Reassembler::new(4);
+        assert!(matches!(
+            error(r.initial(1, "a", 9, b"abc")),
+            ChunkError::TooLarge {
+                size: 9,
+                max_size: 4,
+                ..
+            }
+        ));
+
+        // The rest of it is skipped quietly.
+        pending(r.subsequent(1, 3, b"def"));
+        pending(r.subsequent(1, 6, b"ghi"));
+
+        // Then the id is free again.
+        assert!(matches!(
+            error(r.subsequent(1, 9, b"jkl")),
+            ChunkError::Unexpected { .. }
+        ));
+        assert_eq!(complete(r.initial(1, "a", 4, b"abcd")).1, b"abcd");
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::mpsc;
+
+use anyhow::Result;
+
+use embedded_svc::mqtt::client::{Client, Details, Event, Message, Publish, QoS};
+
+use esp_idf_svc::mqtt::client::{
+    EspMqttClient, EspMqttMessage, LwtConfiguration, MqttClientConfiguration,
+};
+use esp_idf_sys::EspError;
+
+use log::*;
+
+use crate::hardware::get_unique_id;
+
+use super::chunks::{Outcome, Reassembler};
+use super::MqttCommand;
+
+pub struct MqttClient(EspMqttClient);
+
+fn get_qos(qos: super::QoS) -> QoS {
+    match qos {
+        super::QoS::AtMostOnce => QoS::AtMostOnce,
+        super::QoS::AtLeastOnce => QoS::AtLeastOnce,
+        super::QoS::ExactlyOnce => QoS::ExactlyOnce,
+    }
+}
+
+impl MqttClient {
+    pub fn subscribe(&mut self, topic: &str, qos: super::QoS) -> Result<()> {
+        self.0.subscribe(topic, get_qos(qos))?;
+        Ok(())
+    }
+
+    pub fn unsubscribe(&mut self, topic: &str) -> Result<()> {
+        self.0.unsubscribe(topic)?;
+        Ok(())
+    }
+
+    /// Returns the message id to wait for in `Event::Published`, if any.
+    pub fn publish(
+        &mut self,
+        topic: &str,
+        qos: super::QoS,
+        retain: bool,
+        data: &[u8],
+    ) -> Result<Option<u32>> {
+        let id = self.0.publish(topic, get_qos(qos), retain, data)?;
+        match qos {
+            super::QoS::AtMostOnce => Ok(None),
+            _ => Ok(Some(id)),
+        }
+    }
+
+    /// Publishes have been sent by the time `publish` returns, dropping the
+    /// client stops it.
+    pub fn disconnect(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn event_to_string(event: &Event<EspMqttMessage>) -> String {
+    match event {
+        Event::BeforeConnect => "BeforeConnect".to_string(),
+        Event::Connected(connected) => format!("Connected(session: {})", connected),
+        Event::Disconnected => "Disconnected".to_string(),
+        Event::Subscribed(message_id) => format!("Subscribed({})", message_id),
+        Event::Unsubscribed(message_id) => format!("Unsubscribed({})", message_id),
+        Event::Published(message_id) => format!("Published({})", message_id),
+        Event::Received(message) => format!("Received({})", message.id()),
+        Event::Deleted(message_id) => format!("Deleted({})", message_id),
+    }
+}
+
+pub(super) fn get_client(
+    url: &str,
+    lwt_topic: &str,
+    lwt_payload: &str,
+    max_message_size: usize,
+    tx: mpsc::Sender<MqttCommand>,
+) -> Result<MqttClient> {
+    let mut chunks = Reassembler::new(max_message_size);
+
+    let callback = move |msg: &Option<Result<Event<EspMqttMessage>, EspError>>| {
+        let event_or_error = msg.as_ref().unwrap();
+        match event_or_error {
+            Err(e) => info!("MQTT Message ERROR: {}", e),
+            Ok(Event::Received(msg)) => {
+                let outcome = match msg.details() {
+                    Details::Complete(token) => Ok(Outcome::Complete(
+                        msg.topic(token).to_string(),
+                        msg.data().to_vec(),
+                    )),
+                    Details::InitialChunk(chunk) => chunks.initial(
+                        msg.id(),
+                        &msg.topic(&chunk.topic_token),
+                        chunk.total_data_size,
+                        &msg.data(),
+                    ),
+                    Details::SubsequentChunk(chunk) => {
+                        chunks.subsequent(msg.id(), chunk.current_data_offset, &msg.data())
+                    }
+                };
+
+                match outcome {
+                    Ok(Outcome::Complete(topic, data)) => {
+                        tx.send(MqttCommand::MqttReceived(topic, data)).unwrap()
+                    }
+                    Ok(Outcome::Pending) => {}
+                    Err(err) => error!("Dropped MQTT message: {}", err),
+                }
+            }
+            Ok(Event::Connected(_)) => {
+                tx.send(MqttCommand::MqttConnect).unwrap();
+            }
+            Ok(Event::Disconnected) => {
+                for err in chunks.interrupt() {
+                    error!("Dropped MQTT message: {}", err);
+                }
+                tx.send(MqttCommand::MqttDisconnect).unwrap();
+            }
+            Ok(Event::Published(id)) => {
+                tx.send(MqttCommand::Published(*id)).unwrap();
+            }
+            Ok(Event::Subscribed(_x)) => {
+                // Do nothing
+            }
+            Ok(Event::Unsubscribed(_x)) => {
+                // Do nothing
+            }
+            Ok(event) => info!("Got unknown MQTT event {:?}", event_to_string(event)),
+        }
+    };
+
+    let client_id = format!("robotica-remote-rust_{}", get_unique_id());
+    let conf = MqttClientConfiguration {
+        client_id: Some(&client_id),
+        keep_alive_interval: Some(std::time::Duration::new(60, 0)),
+        lwt: Some(LwtConfiguration {
+            topic: lwt_topic,
+            payload: lwt_payload.as_bytes(),
+            qos: QoS::AtMostOnce,
+            retain: true,
+        }),
+        ..Default::default()
+    };
+
+    let client = EspMqttClient::new_with_callback(url, &conf, callback)?;
+    Ok(MqttClient(client))
+}
@@ -0,0 +1,167 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use rumqttc::{Client, Event, LastWill, MqttOptions, Outgoing, Packet, QoS};
+use url::Url;
+
+use log::*;
+
+use crate::hardware::get_unique_id;
+
+use super::MqttCommand;
+
+/// rumqttc doesn't tell us the packet id of a publish, but sends them in
+/// order. Our ids are queued here and matched up with the packet ids as the
+/// publishes go out.
+type SentIds = Arc<Mutex<VecDeque<u32>>>;
+
+/// How long to wait for everything to be sent when disconnecting.
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct MqttClient {
+    client: Client,
+    next_id: u32,
+    sent: SentIds,
+    /// Closed once the connection thread has finished.
+    finished: mpsc::Receiver<()>,
+}
+
+fn get_qos(qos: super::QoS) -> QoS {
+    match qos {
+        super::QoS::AtMostOnce => QoS::AtMostOnce,
+        super::QoS::AtLeastOnce => QoS::AtLeastOnce,
+        super::QoS::ExactlyOnce => QoS::ExactlyOnce,
+    }
+}
+
+impl MqttClient {
+    pub fn subscribe(&mut self, topic: &str, qos: super::QoS) -> Result<()> {
+        self.client.subscribe(topic, get_qos(qos))?;
+        Ok(())
+    }
+
+    pub fn unsubscribe(&mut self, topic: &str) -> Result<()> {
+        self.client.unsubscribe(topic)?;
+        Ok(())
+    }
+
+    /// Returns the message id that will be sent as `MqttCommand::Published`,
+    /// if any.
+    pub fn publish(
+        &mut self,
+        topic: &str,
+        qos: super::QoS,
+        retain: bool,
+        data: &[u8],
+    ) -> Result<Option<u32>> {
+        if qos == super::QoS::AtMostOnce {
+            self.client.publish(topic, QoS::AtMostOnce, retain, data)?;
+            return Ok(None);
+        }
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.sent.lock().unwrap().push_back(id);
+
+        if let Err(err) = self.client.publish(topic, get_qos(qos), retain, data) {
+            self.sent.lock().unwrap().pop_back();
+            return Err(err.into());
+        }
+
+        Ok(Some(id))
+    }
+
+    /// Disconnect once everything already queued has been sent.
+    pub fn disconnect(mut self) -> Result<()> {
+        self.client.disconnect()?;
+        match self.finished.recv_timeout(DISCONNECT_TIMEOUT) {
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(anyhow!("timed out")),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub(super) fn get_client(
+    url: &str,
+    lwt_topic: &str,
+    lwt_payload: &str,
+    max_message_size: usize,
+    tx: mpsc::Sender<MqttCommand>,
+) -> Result<MqttClient> {
+    let url = Url::parse(url)?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("No host in MQTT URL {}", url))?;
+    let port = url.port().unwrap_or(1883);
+
+    let client_id = format!("robotica-remote-rust_{}", get_unique_id());
+    let mut options = MqttOptions::new(client_id, host, port);
+    options.set_keep_alive(Duration::new(60, 0));
+    // rumqttc always delivers complete messages, the limit is on the whole
+    // packet so leave some room for the topic.
+    let max_packet_size = max_message_size + 1024;
+    options.set_max_packet_size(max_packet_size, max_packet_size);
+    options.set_last_will(LastWill::new(lwt_topic, lwt_payload, QoS::AtMostOnce, true));
+    if !url.username().is_empty() {
+        options.set_credentials(url.username(), url.password().unwrap_or(""));
+    }
+
+    let (client, mut connection) = Client::new(options, 10);
+    let sent: SentIds = Arc::new(Mutex::new(VecDeque::new()));
+    let sent_copy = sent.clone();
+    let (finished_tx, finished) = mpsc::channel::<()>();
+
+    thread::spawn(move || {
+        let _finished_tx = finished_tx;
+        let mut packet_ids: HashMap<u16, u32> = HashMap::new();
+
+        for notification in connection.iter() {
+            match notification {
+                Ok(Event::Outgoing(Outgoing::Publish(pkid))) if pkid != 0 => {
+                    if let Some(id) = sent_copy.lock().unwrap().pop_front() {
+                        packet_ids.insert(pkid, id);
+                    }
+                }
+                Ok(Event::Incoming(Packet::PubAck(ack))) => {
+                    if let Some(id) = packet_ids.remove(&ack.pkid) {
+                        tx.send(MqttCommand::Published(id)).unwrap();
+                    }
+                }
+                Ok(Event::Incoming(Packet::PubComp(comp))) => {
+                    if let Some(id) = packet_ids.remove(&comp.pkid) {
+                        tx.send(MqttCommand::Published(id)).unwrap();
+                    }
+                }
+                // Everything before the disconnect has been written by now.
+                Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    tx.send(MqttCommand::MqttConnect).unwrap();
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    let data = publish.payload.to_vec();
+                    tx.send(MqttCommand::MqttReceived(publish.topic, data))
+                        .unwrap();
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    // Reconnecting is up to the MQTT thread, which will
+                    // create a new client.
+                    error!("MQTT connection error: {}", err);
+                    let _ = tx.send(MqttCommand::MqttDisconnect);
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(MqttClient {
+        client,
+        next_id: 1,
+        sent,
+        finished,
+    })
+}
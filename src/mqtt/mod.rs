@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use log::*;
+use serde::Deserialize;
+
+use crate::{app::MqttOutput, messages};
+
+#[cfg(feature = "esp32")]
+mod esp;
+
+#[cfg(feature = "esp32")]
+use esp as client;
+
+#[cfg(feature = "host")]
+mod host;
+
+#[cfg(feature = "host")]
+use host as client;
+
+// Only the ESP32 client gets messages in chunks, but the reassembly itself
+// doesn't depend on the board.
+#[cfg_attr(not(feature = "esp32"), allow(dead_code))]
+mod chunks;
+mod reconnect;
+
+pub use reconnect::ReconnectPolicy;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Backoff(u32),
+    Failed,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "u8")]
+pub enum QoS {
+    #[default]
+    AtMostOnce,
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+impl TryFrom<u8> for QoS {
+    type Error = String;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(QoS::AtMostOnce),
+            1 => Ok(QoS::AtLeastOnce),
+            2 => Ok(QoS::ExactlyOnce),
+            _ => Err(format!("invalid qos {}, expected 0, 1 or 2", v)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Label {
+    Button(usize, u32),
+    NightStatus,
+    Config,
+    Restart,
+}
+
+struct Subscription {
+    label: Label,
+    qos: QoS,
+}
+
+type Subscriptions = HashMap<String, Vec<Subscription>>;
+
+fn get_max_qos(list: &[Subscription]) -> QoS {
+    list.iter().map(|s| s.qos).max().unwrap_or_default()
+}
+
+/// A QoS 1 or 2 publish that has not been acknowledged yet.
+struct PendingPublish {
+    topic: String,
+    deadline: Instant,
+}
+
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(30);
+pub struct Mqtt {
+    tx: mpsc::Sender<MqttCommand>,
+}
+
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+
+enum MqttCommand {
+    MqttConnect,
+    MqttDisconnect,
+    MqttReceived(String, Vec<u8>),
+    Published(u32),
+    Subscribe(String, Label, QoS),
+    Unsubscribe(String, Label),
+    Publish(String, QoS, bool, String),
+    Offline(mpsc::Sender<()>),
+}
+
+impl Mqtt {
+    pub fn connect(
+        url: &str,
+        availability_topic: &str,
+        policy: ReconnectPolicy,
+        max_message_size: usize,
+        tx_to_client: messages::Sender,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let url = url.to_string();
+        let availability_topic = availability_topic.to_string();
+
+        let tx_copy = tx.clone();
+
+        thread::spawn(move || {
+            let mut client: Option<client::MqttClient> = None;
+            let mut subscriptions: Subscriptions = HashMap::new();
+            let mut pending: HashMap<u32, PendingPublish> = HashMap::new();
+            let mut attempt: u32 = 0;
+            let mut reconnect_at: Option<Instant> = Some(Instant::now());
+
+            let send_state = |state: ConnectionState| {
+                info!("MQTT state {:?}", state);
+                tx_to_client
+                    .send(messages::Message::MqttState(state))
+                    .unwrap();
+            };
+
+            let send_failed = |topic: String| {
+                error!("Publish to {} failed", topic);
+                tx_to_client
+                    .send(messages::Message::MqttPublishFailed(topic))
+                    .unwrap();
+            };
+
+            let schedule_reconnect = |attempt: u32| {
+                if policy.is_exhausted(attempt) {
+                    send_state(ConnectionState::Failed);
+                    None
+                } else {
+                    let delay = policy.get_delay(attempt);
+                    info!("Reconnecting to MQTT in {:?}", delay);
+                    send_state(ConnectionState::Backoff(attempt));
+                    Some(Instant::now() + delay)
+                }
+            };
+
+            loop {
+                let now = Instant::now();
+                let expired: Vec<u32> = pending
+                    .iter()
+                    .filter(|(_, p)| p.deadline <= now)
+                    .map(|(id, _)| *id)
+                    .collect();
+                for id in expired {
+                    send_failed(pending.remove(&id).unwrap().topic);
+                }
+
+                if let Some(at) = reconnect_at {
+                    if Instant::now() >= at {
+                        send_state(ConnectionState::Connecting);
+                        match client::get_client(
+                            &url,
+                            &availability_topic,
+                            OFFLINE,
+                            max_message_size,
+                            tx_copy.clone(),
+                        ) {
+                            Ok(new_client) => {
+                                client = Some(new_client);
+                                reconnect_at = None;
+                            }
+                            Err(err) => {
+                                error!("Cannot create MQTT client: {}", err);
+                                attempt = attempt.saturating_add(1);
+                                reconnect_at = schedule_reconnect(attempt);
+                            }
+                        }
+                    }
+                }
+
+                let deadline = reconnect_at
+                    .into_iter()
+                    .chain(pending.values().map(|p| p.deadline))
+                    .min();
+
+                let received = match deadline {
+                    Some(at) => match rx.recv_timeout(at.saturating_duration_since(Instant::now()))
+                    {
+                        Ok(received) => received,
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    },
+                    None => match rx.recv() {
+                        Ok(received) => received,
+                        Err(_) => break,
+                    },
+                };
+
+                match received {
+                    MqttCommand::MqttConnect => {
+                        if let Some(client) = &mut client {
+                            for (topic, list) in subscriptions.iter() {
+                                if let Err(err) = client.subscribe(topic, get_max_qos(list)) {
+                                    error!("Cannot subscribe to {}: {}", topic, err);
+                                }
+                            }
+                            if let Err(err) = client.publish(
+                                &availability_topic,
+                                QoS::AtMostOnce,
+                                true,
+                                ONLINE.as_bytes(),
+                            ) {
+                                error!("Cannot publish {}: {}", availability_topic, err);
+                            }
+                        }
+                        attempt = 0;
+                        send_state(ConnectionState::Connected);
+                        tx_to_client.send(messages::Message::MqttConnect).unwrap();
+                    }
+
+                    MqttCommand::MqttDisconnect => {
+                        // Ignore repeated events from a client that is already gone.
+                        if client.take().is_some() {
+                            for (_, p) in pending.drain() {
+                                send_failed(p.topic);
+                            }
+                            tx_to_client
+                                .send(messages::Message::MqttDisconnect)
+                                .unwrap();
+                            attempt = attempt.saturating_add(1);
+                            reconnect_at = schedule_reconnect(attempt);
+                        }
+                    }
+
+                    MqttCommand::MqttReceived(topic, data) => {
+                        if let Some(list) = subscriptions.get(&topic) {
+                            for s in list {
+                                tx_to_client
+                                    .send(messages::Message::MqttReceived(
+                                        topic.clone(),
+                                        data.clone(),
+                                        s.label.clone(),
+                                    ))
+                                    .unwrap();
+                            }
+                        }
+                    }
+
+                    MqttCommand::Published(id) => {
+                        if let Some(p) = pending.remove(&id) {
+                            debug!("Published {} to {}", id, p.topic);
+                        }
+                    }
+
+                    MqttCommand::Subscribe(topic, label, qos) => {
+                        let subscription = Subscription { label, qos };
+
+                        let list = subscriptions.entry(topic.clone()).or_default();
+                        let old_qos = if list.is_empty() {
+                            None
+                        } else {
+                            Some(get_max_qos(list))
+                        };
+                        list.push(subscription);
+
+                        // Subscribe again if the topic needs a higher QoS.
+                        if old_qos.map_or(true, |old_qos| qos > old_qos) {
+                            if let Some(client) = &mut client {
+                                if let Err(err) = client.subscribe(&topic, qos) {
+                                    error!("Cannot subscribe to {}: {}", topic, err);
+                                }
+                            }
+                        }
+                    }
+
+                    MqttCommand::Unsubscribe(topic, label) => {
+                        if let Some(list) = subscriptions.get_mut(&topic) {
+                            list.retain(|s| s.label != label);
+                            if list.is_empty() {
+                                subscriptions.remove(&topic);
+                                if let Some(client) = &mut client {
+                                    if let Err(err) = client.unsubscribe(&topic) {
+                                        error!("Cannot unsubscribe from {}: {}", topic, err);
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    MqttCommand::Publish(topic, qos, retain, data) => {
+                        debug!("Publishing {} {}", topic, data);
+                        let result = match &mut client {
+                            Some(client) => client.publish(&topic, qos, retain, data.as_bytes()),
+                            None => Err(anyhow!("not connected")),
+                        };
+
+                        match result {
+                            Ok(Some(id)) => {
+                                let deadline = Instant::now() + PUBLISH_TIMEOUT;
+                                pending.insert(id, PendingPublish { topic, deadline });
+                            }
+                            Ok(None) => {}
+                            Err(err) => {
+                                error!("Cannot publish {}: {}", topic, err);
+                                if qos != QoS::AtMostOnce {
+                                    send_failed(topic);
+                                }
+                            }
+                        }
+                    }
+
+                    MqttCommand::Offline(done) => {
+                        // Disconnecting waits for the message to go out, and
+                        // there is no reconnecting after this.
+                        if let Some(mut client) = client.take() {
+                            if let Err(err) = client.publish(
+                                &availability_topic,
+                                QoS::AtMostOnce,
+                                true,
+                                OFFLINE.as_bytes(),
+                            ) {
+                                error!("Cannot publish {}: {}", availability_topic, err);
+                            }
+                            if let Err(err) = client.disconnect() {
+                                error!("Cannot disconnect from MQTT: {}", err);
+                            }
+                        }
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+
+        Mqtt { tx }
+    }
+
+    pub fn subscribe(&self, topic: &str, label: Label, qos: QoS) {
+        let tx = self.tx.clone();
+        tx.send(MqttCommand::Subscribe(topic.to_string(), label, qos))
+            .unwrap();
+    }
+
+    pub fn unsubscribe(&self, topic: &str, label: Label) {
+        let tx = self.tx.clone();
+        tx.send(MqttCommand::Unsubscribe(topic.to_string(), label))
+            .unwrap();
+    }
+
+    pub fn publish(&self, topic: &str, qos: QoS, retain: bool, data: &str) {
+        let tx = self.tx.clone();
+        tx.send(MqttCommand::Publish(
+            topic.to_string(),
+            qos,
+            retain,
+            data.to_string(),
+        ))
+        .unwrap();
+    }
+
+    pub fn offline(&self) {
+        let (tx, rx) = mpsc::channel();
+        self.tx.send(MqttCommand::Offline(tx)).unwrap();
+        let _ = rx.recv();
+    }
+}
+
+impl MqttOutput for Mqtt {
+    fn subscribe(&self, topic: &str, label: Label, qos: QoS) {
+        Mqtt::subscribe(self, topic, label, qos);
+    }
+
+    fn unsubscribe(&self, topic: &str, label: Label) {
+        Mqtt::unsubscribe(self, topic, label);
+    }
+
+    fn publish(&self, topic: &str, qos: QoS, retain: bool, data: &str) {
+        Mqtt::publish(self, topic, qos, retain, data);
+    }
+
+    fn offline(&self) {
+        Mqtt::offline(self);
+    }
+}
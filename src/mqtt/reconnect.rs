@@ -0,0 +1,111 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Fraction of the delay that is randomly added or removed, so that a
+    /// house full of remotes doesn't reconnect at exactly the same time.
+    pub jitter: f32,
+    /// Give up after this many failed attempts, or never if `None`.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: 0.25,
+            max_attempts: None,
+        }
+    }
+}
+
+fn random() -> f32 {
+    // RandomState is seeded differently every time, which is random enough
+    // for jitter and saves pulling in a rand crate.
+    let hasher = RandomState::new().build_hasher();
+    (hasher.finish() % 10_000) as f32 / 10_000.0
+}
+
+impl ReconnectPolicy {
+    pub fn is_exhausted(&self, attempt: u32) -> bool {
+        match self.max_attempts {
+            Some(max_attempts) => attempt >= max_attempts,
+            None => false,
+        }
+    }
+
+    /// The delay before reconnect attempt `attempt`, counting from 1.
+    pub fn get_delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let delay = self
+            .initial_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        let jitter = self.jitter.clamp(0.0, 1.0) * (random() * 2.0 - 1.0);
+        delay.mul_f32(1.0 + jitter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(jitter: f32, max_attempts: Option<u32>) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter,
+            max_attempts,
+        }
+    }
+
+    #[test]
+    fn delay_doubles_each_attempt() {
+        let policy = policy(0.0, None);
+        let delays: Vec<u64> = (1..=6)
+            .map(|attempt| policy.get_delay(attempt).as_secs())
+            .collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32]);
+    }
+
+    #[test]
+    fn delay_is_capped() {
+        let policy = policy(0.0, None);
+        assert_eq!(policy.get_delay(7), Duration::from_secs(60));
+        assert_eq!(policy.get_delay(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let policy = policy(0.25, None);
+        for _ in 0..1000 {
+            let delay = policy.get_delay(3);
+            assert!(delay >= Duration::from_secs(3), "{:?}", delay);
+            assert!(delay <= Duration::from_secs(5), "{:?}", delay);
+
+            // The cap applies before the jitter.
+            let delay = policy.get_delay(10);
+            assert!(delay >= Duration::from_secs(45), "{:?}", delay);
+            assert!(delay <= Duration::from_secs(75), "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn exhausted_after_max_attempts() {
+        let policy = policy(0.0, Some(3));
+        assert!(!policy.is_exhausted(2));
+        assert!(policy.is_exhausted(3));
+        assert!(policy.is_exhausted(4));
+    }
+
+    #[test]
+    fn never_exhausted_without_a_limit() {
+        assert!(!policy(0.0, None).is_exhausted(u32::MAX));
+    }
+}
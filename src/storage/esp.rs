@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use embedded_svc::storage::RawStorage;
+
+use esp_idf_svc::nvs::EspDefaultNvs;
+use esp_idf_svc::nvs_storage::EspNvsStorage;
+
+use crate::app::ConfigStore;
+
+const NAMESPACE: &str = "remote";
+const CONFIG_KEY: &str = "config";
+
+pub struct ConfigStorage {
+    storage: EspNvsStorage,
+}
+
+impl ConfigStorage {
+    pub fn new(default_nvs: Arc<EspDefaultNvs>) -> Result<Self> {
+        let storage = EspNvsStorage::new_default(default_nvs, NAMESPACE, true)?;
+        Ok(ConfigStorage { storage })
+    }
+
+    pub fn load(&self) -> Result<Option<String>> {
+        self.get(CONFIG_KEY)
+    }
+
+    pub fn save(&mut self, config: &str) -> Result<()> {
+        self.put(CONFIG_KEY, config)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        let len = match self.storage.len(key)? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+
+        let mut buf = vec![0; len];
+        match self.storage.get_raw(key, &mut buf)? {
+            Some(data) => Ok(Some(std::str::from_utf8(data)?.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    fn put(&mut self, key: &str, data: &str) -> Result<()> {
+        self.storage.put_raw(key, data.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl ConfigStore for ConfigStorage {
+    fn save(&mut self, config: &str) -> Result<()> {
+        ConfigStorage::save(self, config)
+    }
+
+    fn save_state(&mut self, key: &str, state: &str) -> Result<()> {
+        self.put(key, state)
+    }
+
+    fn load_state(&self, key: &str) -> Result<Option<String>> {
+        self.get(key)
+    }
+}
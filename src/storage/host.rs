@@ -0,0 +1,54 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::app::ConfigStore;
+
+pub struct ConfigStorage {
+    path: PathBuf,
+}
+
+impl ConfigStorage {
+    pub fn new(path: PathBuf) -> Result<Self> {
+        Ok(ConfigStorage { path })
+    }
+
+    pub fn load(&self) -> Result<Option<String>> {
+        read(&self.path)
+    }
+
+    pub fn save(&mut self, config: &str) -> Result<()> {
+        fs::write(&self.path, config)?;
+        Ok(())
+    }
+
+    /// State is kept next to the config, e.g. `config.state0.json`.
+    fn state_path(&self, key: &str) -> PathBuf {
+        self.path.with_extension(format!("{}.json", key))
+    }
+}
+
+fn read(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+impl ConfigStore for ConfigStorage {
+    fn save(&mut self, config: &str) -> Result<()> {
+        ConfigStorage::save(self, config)
+    }
+
+    fn save_state(&mut self, key: &str, state: &str) -> Result<()> {
+        fs::write(self.state_path(key), state)?;
+        Ok(())
+    }
+
+    fn load_state(&self, key: &str) -> Result<Option<String>> {
+        read(&self.state_path(key))
+    }
+}
@@ -0,0 +1,11 @@
+#[cfg(feature = "esp32")]
+pub mod esp;
+
+#[cfg(feature = "esp32")]
+pub use esp::ConfigStorage;
+
+#[cfg(feature = "host")]
+pub mod host;
+
+#[cfg(feature = "host")]
+pub use host::ConfigStorage;
@@ -20,15 +20,15 @@ use crate::hardware::esp32::get_unique_id;
 const SSID: &str = env!("WIFI_SSID");
 const PASS: &str = env!("WIFI_PASS");
 
-pub fn connect() -> Result<(EspWifi, EspSntp)> {
+pub fn connect() -> Result<(EspWifi, EspSntp, Arc<EspDefaultNvs>)> {
     let netif_stack = Arc::new(EspNetifStack::new()?);
     let sys_loop_stack = Arc::new(EspSysLoopStack::new()?);
     let default_nvs = Arc::new(EspDefaultNvs::new()?);
 
-    let wifi = wifi(netif_stack, sys_loop_stack, default_nvs)?;
+    let wifi = wifi(netif_stack, sys_loop_stack, default_nvs.clone())?;
     let sntp = EspSntp::new_default()?;
 
-    Ok((wifi, sntp))
+    Ok((wifi, sntp, default_nvs))
 }
 
 fn wifi(